@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::SurepetClient;
+use crate::entities::account::Account;
+use crate::error::Result;
+
+const ME_PATH: &str = "/api/me";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MeResponse {
+    data: UserData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct UserData {
+    id: u64,
+    email_address: String,
+    #[serde(default)]
+    first_name: String,
+    #[serde(default)]
+    last_name: String,
+}
+
+impl SurepetClient {
+    /// Retrieve the account the client is logged in with.
+    pub async fn me(&self) -> Result<Account> {
+        let parsed: MeResponse = self.get_resources(ME_PATH).await?;
+        let user = parsed.data;
+
+        Ok(Account {
+            id: user.id,
+            email: user.email_address,
+            name: format!("{} {}", user.first_name, user.last_name)
+                .trim()
+                .to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+use mockito;
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::authenticated_client;
+    #[cfg(test)]
+    use mockito::mock;
+
+    #[test]
+    fn it_returns_the_account() {
+        let _m = mock("GET", ME_PATH)
+            .with_status(200)
+            .with_body(
+                r#"{"data": {"id": 1234, "email_address": "me@example.com", "first_name": "Jane", "last_name": "Doe"}}"#,
+            )
+            .create();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().me()).unwrap(),
+            Account {
+                id: 1234,
+                email: "me@example.com".to_string(),
+                name: "Jane Doe".to_string(),
+            }
+        );
+    }
+}
@@ -1,11 +1,28 @@
-use std::vec;
+use chrono::NaiveTime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 
-use serde::{Deserialize, Serialize};
+use crate::client::SurepetClient;
+use crate::entities::{
+    curfew::Curfew,
+    feeder::Feeder,
+    felaqua::Felaqua,
+    flap::{Flap, FlapProduct, LockMode},
+    hub::Hub,
+    shared::Information,
+    unknown_device::UnknownDevice,
+};
+use crate::error::{Result, SurepetError};
 
-use crate::api::get_resources;
-use crate::entities::{flap::Flap, hub::Hub, shared::Information};
+pub(crate) const DEVICES_PATH: &str = "/api/device?with=status,control";
 
-const DEVICES_PATH: &str = "/api/device?with=status";
+/// Product IDs used by the API to distinguish devices.
+const HUB: u8 = 1;
+const PET_DOOR: u8 = 3;
+const FEEDER: u8 = 4;
+const CAT_FLAP: u8 = 6;
+const FEEDER_LITE: u8 = 7;
+const FELAQUA: u8 = 8;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct DevicesResponse {
@@ -13,47 +30,239 @@ struct DevicesResponse {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct DeviceData {
+pub(crate) struct DeviceData {
+    id: u64,
+    household_id: u64,
     name: String,
     product_id: u8, // Internal ID used to distinguish between the hub, a flap, …
-    status: DeviceStatusData,
+    // Decoded once the product is known, as each product describes them its own way.
+    status: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    control: Option<Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct DeviceStatusData {
+pub(crate) struct DeviceStatusData {
     #[serde(skip_serializing_if = "Option::is_none")]
     battery: Option<f64>,
     online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) locking: Option<LockingData>, // Only for flaps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bowl_status: Option<Vec<BowlStatusData>>, // Only for feeders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    water: Option<WaterData>, // Only for water stations.
 }
 
-/// Retrieve devices from api and convert them to the proper struct.
-pub async fn devices() -> Vec<Box<dyn Information>> {
-    let mut result: Vec<Box<dyn Information>> = vec![];
-
-    match get_resources(DEVICES_PATH)
-        .await
-        .json::<DevicesResponse>()
-        .await
-    {
-        Ok(parsed) => {
-            for device in parsed.data {
-                match device.product_id {
-                    1 => result.push(Box::new(Hub {
-                        name: device.name,
-                        online: device.status.online,
-                    })),
-                    3 => result.push(Box::new(Flap {
-                        name: device.name,
-                        online: device.status.online,
-                        battery_voltage: device.status.battery.unwrap(),
-                    })),
-                    _ => panic!("This device is unknown!"),
-                }
+/// Settings of a device, as opposed to its status.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ControlData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) curfew: Option<CurfewsData>, // Only for flaps.
+}
+
+/// The pet door has a single curfew, the cat flap a list of them.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum CurfewsData {
+    Many(Vec<CurfewData>),
+    One(CurfewData),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CurfewData {
+    pub(crate) enabled: bool,
+    pub(crate) lock_time: String,
+    pub(crate) unlock_time: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct LockingData {
+    pub(crate) mode: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BowlStatusData {
+    index: u8,
+    current_weight: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct WaterData {
+    weight: f64,
+}
+
+impl SurepetClient {
+    /// Retrieve devices from api and convert them to the proper struct.
+    ///
+    /// Devices we don't support yet are kept as [`UnknownDevice`], unless the client is strict.
+    pub async fn devices(&self) -> Result<Vec<Box<dyn Information>>> {
+        Ok(self.devices_and_flaps().await?.0)
+    }
+
+    /// Retrieve devices, along with the flaps among them, with a single request.
+    pub(crate) async fn devices_and_flaps(&self) -> Result<(Vec<Box<dyn Information>>, Vec<Flap>)> {
+        let parsed: DevicesResponse = self.get_resources(DEVICES_PATH).await?;
+        let mut devices = vec![];
+        let mut flaps = vec![];
+
+        for (index, device) in parsed.data.into_iter().enumerate() {
+            if !self.in_household(device.household_id) {
+                continue;
+            }
+            if matches!(device.product_id, PET_DOOR | CAT_FLAP) {
+                let flap = flap(index, device)?;
+                devices.push(Box::new(flap.clone()) as Box<dyn Information>);
+                flaps.push(flap);
+            } else {
+                devices.push(self.device(index, device)?);
             }
         }
-        Err(_) => panic!("Hm, the response didn't match the shape we expected."),
+        Ok((devices, flaps))
+    }
+
+    /// Retrieve flaps only from api.
+    pub async fn flaps(&self) -> Result<Vec<Flap>> {
+        let parsed: DevicesResponse = self.get_resources(DEVICES_PATH).await?;
+
+        parsed
+            .data
+            .into_iter()
+            .enumerate()
+            .filter(|(_, device)| {
+                matches!(device.product_id, PET_DOOR | CAT_FLAP)
+                    && self.in_household(device.household_id)
+            })
+            .map(|(index, device)| flap(index, device))
+            .collect()
+    }
+
+    fn device(&self, index: usize, device: DeviceData) -> Result<Box<dyn Information>> {
+        match device.product_id {
+            HUB => Ok(Box::new(Hub {
+                id: device.id,
+                household_id: device.household_id,
+                online: status(index, device.status)?.online,
+                name: device.name,
+            })),
+            PET_DOOR | CAT_FLAP => Ok(Box::new(flap(index, device)?)),
+            FEEDER | FEEDER_LITE => {
+                let status = status(index, device.status)?;
+                let mut bowls = status
+                    .bowl_status
+                    .ok_or_else(|| missing(index, "bowl_status"))?;
+                bowls.sort_by_key(|bowl| bowl.index);
+
+                Ok(Box::new(Feeder {
+                    id: device.id,
+                    name: device.name,
+                    online: status.online,
+                    battery_voltage: status.battery.ok_or_else(|| missing(index, "battery"))?,
+                    bowl_weights: bowls.iter().map(|bowl| bowl.current_weight).collect(),
+                }))
+            }
+            FELAQUA => {
+                let status = status(index, device.status)?;
+
+                Ok(Box::new(Felaqua {
+                    id: device.id,
+                    name: device.name,
+                    online: status.online,
+                    battery_voltage: status.battery.ok_or_else(|| missing(index, "battery"))?,
+                    water_weight: status.water.ok_or_else(|| missing(index, "water"))?.weight,
+                }))
+            }
+            product_id if self.strict => Err(SurepetError::UnknownProduct(product_id)),
+            product_id => Ok(Box::new(UnknownDevice {
+                id: device.id,
+                name: device.name,
+                product_id,
+                online: device.status["online"].as_bool().unwrap_or_default(),
+                status: device.status,
+            })),
+        }
+    }
+}
+
+fn flap(index: usize, device: DeviceData) -> Result<Flap> {
+    let status = status(index, device.status)?;
+
+    Ok(Flap {
+        id: device.id,
+        household_id: device.household_id,
+        name: device.name,
+        online: status.online,
+        battery_voltage: status.battery.ok_or_else(|| missing(index, "battery"))?,
+        lock_mode: LockMode::from_api(
+            status
+                .locking
+                .ok_or_else(|| missing(index, "locking"))?
+                .mode,
+        ),
+        product: match device.product_id {
+            CAT_FLAP => FlapProduct::CatFlap,
+            _ => FlapProduct::PetDoor,
+        },
+        curfews: curfews(
+            device
+                .control
+                .map(|control| decode::<ControlData>(control, format!("data[{}].control", index)))
+                .transpose()?
+                .and_then(|control| control.curfew),
+            &format!("data[{}].control.curfew", index),
+        )?,
+    })
+}
+
+/// Curfews from the API representation, failing on invalid times.
+pub(crate) fn curfews(curfews: Option<CurfewsData>, path: &str) -> Result<Vec<Curfew>> {
+    let curfews = match curfews {
+        None => vec![],
+        Some(CurfewsData::One(curfew)) => vec![curfew],
+        Some(CurfewsData::Many(curfews)) => curfews,
+    };
+
+    curfews
+        .into_iter()
+        .enumerate()
+        .map(|(index, curfew)| {
+            let time = |time: &str, field: &str| {
+                NaiveTime::parse_from_str(time, "%H:%M").map_err(|error| SurepetError::Decode {
+                    path: format!("{}[{}].{}", path, index, field),
+                    message: error.to_string(),
+                })
+            };
+
+            Ok(Curfew {
+                enabled: curfew.enabled,
+                lock_time: time(&curfew.lock_time, "lock_time")?,
+                unlock_time: time(&curfew.unlock_time, "unlock_time")?,
+            })
+        })
+        .collect()
+}
+
+/// Status of a supported device, decoded once its product is known.
+fn status(index: usize, status: Value) -> Result<DeviceStatusData> {
+    decode(status, format!("data[{}].status", index))
+}
+
+/// Decode a part of a device found at `path` of the response.
+fn decode<T: DeserializeOwned>(value: Value, path: String) -> Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|error| SurepetError::Decode {
+        path: match error.path().to_string().as_str() {
+            "." => path,
+            inner => format!("{}.{}", path, inner),
+        },
+        message: error.inner().to_string(),
+    })
+}
+
+fn missing(index: usize, field: &str) -> SurepetError {
+    SurepetError::Decode {
+        path: format!("data[{}].status.{}", index, field),
+        message: format!("missing field `{}`", field),
     }
-    return result;
 }
 
 #[cfg(test)]
@@ -63,6 +272,8 @@ mod tests {
     #[cfg(test)]
     use super::*;
     #[cfg(test)]
+    use crate::api::authenticated_client;
+    #[cfg(test)]
     use mockito::mock;
 
     #[test]
@@ -75,6 +286,7 @@ mod tests {
                     "data": [
                         {
                             "id": 123,
+                            "household_id": 42,
                             "product_id": 1,
                             "name": "Hub",
                             "status": {
@@ -85,6 +297,7 @@ mod tests {
                         },
                         {
                             "id": 456,
+                            "household_id": 42,
                             "product_id": 3,
                             "name": "Flap",
                             "status": {
@@ -93,6 +306,13 @@ mod tests {
                                     "mode": 0
                                 },
                                 "online": true
+                            },
+                            "control": {
+                                "curfew": {
+                                    "enabled": false,
+                                    "lock_time": "20:00",
+                                    "unlock_time": "06:00"
+                                }
                             }
                         }
                     ]
@@ -103,22 +323,320 @@ mod tests {
 
         let expected: Vec<Box<dyn Information>> = vec![
             Box::new(Hub {
+                id: 123,
+                household_id: 42,
                 name: "Hub".to_string(),
                 online: true,
             }),
             Box::new(Flap {
+                id: 456,
+                household_id: 42,
                 name: "Flap".to_string(),
                 online: true,
                 battery_voltage: 5.6175,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                curfews: vec![Curfew {
+                    enabled: false,
+                    ..("20:00-06:00".parse().unwrap())
+                }],
             }),
         ];
 
-        assert_eq!(tokio_test::block_on(devices()), expected);
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().devices()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_returns_feeders_water_stations_and_cat_flaps_information() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 789,
+                            "household_id": 42,
+                            "product_id": 6,
+                            "name": "Cat flap",
+                            "status": {
+                                "battery": 5.8,
+                                "locking": {
+                                    "mode": 1
+                                },
+                                "online": false
+                            },
+                            "control": {
+                                "curfew": [
+                                    {
+                                        "enabled": true,
+                                        "lock_time": "19:00",
+                                        "unlock_time": "07:00"
+                                    }
+                                ]
+                            }
+                        },
+                        {
+                            "id": 790,
+                            "household_id": 42,
+                            "product_id": 4,
+                            "name": "Feeder",
+                            "status": {
+                                "battery": 5.6175,
+                                "bowl_status": [
+                                    {
+                                        "index": 1,
+                                        "current_weight": 41.0
+                                    },
+                                    {
+                                        "index": 0,
+                                        "current_weight": 23.4
+                                    }
+                                ],
+                                "online": true
+                            }
+                        },
+                        {
+                            "id": 791,
+                            "household_id": 42,
+                            "product_id": 8,
+                            "name": "Felaqua",
+                            "status": {
+                                "battery": 5.9,
+                                "water": {
+                                    "weight": 450.2
+                                },
+                                "online": true
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        let expected: Vec<Box<dyn Information>> = vec![
+            Box::new(Flap {
+                id: 789,
+                household_id: 42,
+                name: "Cat flap".to_string(),
+                online: false,
+                battery_voltage: 5.8,
+                lock_mode: LockMode::In,
+                product: FlapProduct::CatFlap,
+                curfews: vec!["19:00-07:00".parse().unwrap()],
+            }),
+            Box::new(Feeder {
+                id: 790,
+                name: "Feeder".to_string(),
+                online: true,
+                battery_voltage: 5.6175,
+                bowl_weights: vec![23.4, 41.0],
+            }),
+            Box::new(Felaqua {
+                id: 791,
+                name: "Felaqua".to_string(),
+                online: true,
+                battery_voltage: 5.9,
+                water_weight: 450.2,
+            }),
+        ];
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().devices()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_fails_when_feeder_has_no_bowl_status() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 790,
+                            "household_id": 42,
+                            "product_id": 4,
+                            "name": "Feeder",
+                            "status": {
+                                "battery": 5.6175,
+                                "online": true
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        match tokio_test::block_on(authenticated_client().devices()) {
+            Err(SurepetError::Decode { path, .. }) => {
+                assert_eq!(path, "data[0].status.bowl_status")
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_keeps_flaps_in_unknown_lock_modes() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 456,
+                            "household_id": 42,
+                            "product_id": 3,
+                            "name": "Flap",
+                            "status": {
+                                "battery": 5.6175,
+                                "locking": {
+                                    "mode": 9
+                                },
+                                "online": true
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        let expected: Vec<Box<dyn Information>> = vec![Box::new(Flap {
+            id: 456,
+            household_id: 42,
+            name: "Flap".to_string(),
+            online: true,
+            battery_voltage: 5.6175,
+            lock_mode: LockMode::Unknown(9),
+            product: FlapProduct::PetDoor,
+            curfews: vec![],
+        })];
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().devices()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_keeps_unknown_devices() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 123,
+                            "household_id": 42,
+                            "product_id": 42,
+                            "name": "Gadget",
+                            "status": {
+                                "online": true,
+                                "glitter": 3
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        let expected: Vec<Box<dyn Information>> = vec![Box::new(UnknownDevice {
+            id: 123,
+            name: "Gadget".to_string(),
+            product_id: 42,
+            online: true,
+            status: serde_json::json!({"online": true, "glitter": 3}),
+        })];
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().devices()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_keeps_unknown_devices_with_a_status_of_their_own() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 123,
+                            "household_id": 42,
+                            "product_id": 42,
+                            "name": "Gadget",
+                            "status": {
+                                "battery": "full",
+                                "locking": "never"
+                            },
+                            "control": {
+                                "curfew": 3
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        let expected: Vec<Box<dyn Information>> = vec![Box::new(UnknownDevice {
+            id: 123,
+            name: "Gadget".to_string(),
+            product_id: 42,
+            online: false,
+            status: serde_json::json!({"battery": "full", "locking": "never"}),
+        })];
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().devices()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_fails_when_a_supported_device_has_an_invalid_status() {
+        let _m = mock("GET", DEVICES_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 123,
+                            "household_id": 42,
+                            "product_id": 1,
+                            "name": "Hub",
+                            "status": {
+                                "online": "yes"
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        match tokio_test::block_on(authenticated_client().devices()) {
+            Err(SurepetError::Decode { path, .. }) => assert_eq!(path, "data[0].status.online"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
     }
 
     #[test]
-    #[should_panic(expected = "This device is unknown!")]
-    fn it_panics_when_device_is_unknown() {
+    fn it_fails_when_device_is_unknown_and_client_is_strict() {
         let _m = mock("GET", DEVICES_PATH)
             .with_status(200)
             .with_body(
@@ -127,6 +645,7 @@ mod tests {
                     "data": [
                         {
                             "id": 123,
+                            "household_id": 42,
                             "product_id": 0,
                             "name": "Unknown",
                             "status": {
@@ -139,13 +658,18 @@ mod tests {
             )
             .create();
 
-        tokio_test::block_on(devices());
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().with_strict(true).devices()),
+            Err(SurepetError::UnknownProduct(0))
+        ));
     }
 
     #[test]
-    #[should_panic(expected = "Uh oh! Something unexpected happened.")]
-    fn it_panics_when_response_is_not_handled() {
+    fn it_fails_when_response_is_not_handled() {
         let _m = mock("GET", DEVICES_PATH).with_status(500).create();
-        tokio_test::block_on(devices());
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().devices()),
+            Err(SurepetError::Http { .. })
+        ));
     }
 }
@@ -0,0 +1,341 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::devices::{curfews, ControlData, CurfewData, DeviceStatusData};
+use crate::client::SurepetClient;
+use crate::entities::curfew::{validate, Curfew};
+use crate::entities::flap::{Flap, FlapProduct, LockMode};
+use crate::error::{Result, SurepetError};
+
+#[cfg(not(test))]
+/// How long to wait between two checks of a pending change.
+const ACKNOWLEDGEMENT_INTERVAL: Duration = Duration::from_secs(2);
+#[cfg(test)]
+const ACKNOWLEDGEMENT_INTERVAL: Duration = Duration::ZERO;
+/// How many times to check a pending change before giving up.
+const ACKNOWLEDGEMENT_ATTEMPTS: u32 = 15;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DeviceStatusResponse {
+    data: DeviceStatusData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DeviceControlResponse {
+    data: ControlData,
+}
+
+impl From<&Curfew> for CurfewData {
+    fn from(curfew: &Curfew) -> Self {
+        CurfewData {
+            enabled: curfew.enabled,
+            lock_time: curfew.lock_time.format("%H:%M").to_string(),
+            unlock_time: curfew.unlock_time.format("%H:%M").to_string(),
+        }
+    }
+}
+
+fn control_path(flap: &Flap) -> String {
+    format!("/api/device/{}/control", flap.id)
+}
+
+fn status_path(flap: &Flap) -> String {
+    format!("/api/device/{}/status", flap.id)
+}
+
+impl SurepetClient {
+    /// Find a flap by its name, ignoring case.
+    pub async fn flap(&self, name: &str) -> Result<Flap> {
+        self.flaps()
+            .await?
+            .into_iter()
+            .find(|flap| flap.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SurepetError::NotFound(format!("flap `{}`", name)))
+    }
+
+    /// Change the lock mode of a flap.
+    ///
+    /// The change is pending until the hub relays it to the flap, so this waits for the flap to
+    /// report the new lock mode, and returns it.
+    pub async fn set_lock_mode(&self, flap: &Flap, mode: LockMode) -> Result<LockMode> {
+        let _: Value = self
+            .put_resources(&control_path(flap), &json!({ "locking": mode.to_api() }))
+            .await?;
+
+        for _ in 0..ACKNOWLEDGEMENT_ATTEMPTS {
+            let status: DeviceStatusResponse = self.get_resources(&status_path(flap)).await?;
+            let locking = status.data.locking.ok_or_else(|| SurepetError::Decode {
+                path: "data.locking".to_string(),
+                message: "missing field `locking`".to_string(),
+            })?;
+            let current = LockMode::from_api(locking.mode);
+
+            if current == mode {
+                return Ok(current);
+            }
+            tokio::time::sleep(ACKNOWLEDGEMENT_INTERVAL).await;
+        }
+        Err(SurepetError::Unacknowledged(flap.name.clone()))
+    }
+
+    /// Retrieve the curfews of a flap.
+    pub async fn curfews(&self, flap: &Flap) -> Result<Vec<Curfew>> {
+        let control: DeviceControlResponse = self.get_resources(&control_path(flap)).await?;
+
+        curfews(control.data.curfew, "data.curfew")
+    }
+
+    /// Replace the curfews of a flap, and return the new ones.
+    ///
+    /// Curfews must not overlap, and the pet door holds a single one.
+    pub async fn set_curfews(&self, flap: &Flap, curfews: &[Curfew]) -> Result<Vec<Curfew>> {
+        validate(curfews, flap.product.max_curfews()).map_err(SurepetError::InvalidCurfew)?;
+
+        let curfews: Vec<CurfewData> = curfews.iter().map(CurfewData::from).collect();
+        let payload = match (flap.product, curfews.first()) {
+            (FlapProduct::CatFlap, _) => json!({ "curfew": curfews }),
+            (FlapProduct::PetDoor, Some(curfew)) => json!({ "curfew": curfew }),
+            (FlapProduct::PetDoor, None) => {
+                return Err(SurepetError::InvalidCurfew(
+                    "the pet door curfew can be disabled, but not removed".to_string(),
+                ))
+            }
+        };
+        let _: Value = self.put_resources(&control_path(flap), &payload).await?;
+
+        self.curfews(flap).await
+    }
+
+    /// Remove the curfews of a cat flap, or disable the one of a pet door.
+    pub async fn clear_curfews(&self, flap: &Flap) -> Result<Vec<Curfew>> {
+        match flap.product {
+            FlapProduct::CatFlap => self.set_curfews(flap, &[]).await,
+            FlapProduct::PetDoor => {
+                let disabled: Vec<Curfew> = self
+                    .curfews(flap)
+                    .await?
+                    .into_iter()
+                    .map(|curfew| Curfew {
+                        enabled: false,
+                        ..curfew
+                    })
+                    .collect();
+
+                if disabled.is_empty() {
+                    Ok(disabled)
+                } else {
+                    self.set_curfews(flap, &disabled).await
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use mockito;
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::authenticated_client;
+    #[cfg(test)]
+    use crate::entities::test_support;
+    #[cfg(test)]
+    use mockito::{mock, Matcher};
+
+    #[cfg(test)]
+    fn flap() -> Flap {
+        Flap {
+            battery_voltage: 5.6175,
+            lock_mode: LockMode::None,
+            product: FlapProduct::PetDoor,
+            ..test_support::flap()
+        }
+    }
+
+    #[cfg(test)]
+    fn status(mode: u8) -> String {
+        format!(
+            r#"{{"data": {{"online": true, "battery": 5.6175, "locking": {{"mode": {}}}}}}}"#,
+            mode
+        )
+    }
+
+    #[test]
+    fn it_finds_a_flap_by_name() {
+        let _m = mock("GET", "/api/device?with=status,control")
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 123,
+                            "household_id": 42,
+                            "product_id": 1,
+                            "name": "Hub",
+                            "status": {
+                                "online": true
+                            }
+                        },
+                        {
+                            "id": 456,
+                            "household_id": 42,
+                            "product_id": 3,
+                            "name": "Buanderie",
+                            "status": {
+                                "battery": 5.6175,
+                                "locking": {
+                                    "mode": 0
+                                },
+                                "online": true
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        let client = authenticated_client();
+        assert_eq!(
+            tokio_test::block_on(client.flap("buanderie")).unwrap(),
+            flap()
+        );
+        assert!(matches!(
+            tokio_test::block_on(client.flap("Hub")),
+            Err(SurepetError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn it_waits_for_the_lock_mode_to_be_acknowledged() {
+        let control = mock("PUT", "/api/device/456/control")
+            .match_body(Matcher::Json(json!({"locking": 1})))
+            .with_status(200)
+            .with_body(r#"{"data": {"locking": 1}}"#)
+            .create();
+        let pending = mock("GET", "/api/device/456/status")
+            .with_status(200)
+            .with_body(status(0))
+            .expect(1)
+            .create();
+        let acknowledged = mock("GET", "/api/device/456/status")
+            .with_status(200)
+            .with_body(status(1))
+            .create();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().set_lock_mode(&flap(), LockMode::In))
+                .unwrap(),
+            LockMode::In
+        );
+        control.assert();
+        pending.assert();
+        acknowledged.assert();
+    }
+
+    #[test]
+    fn it_gives_up_when_the_lock_mode_is_never_acknowledged() {
+        let _control = mock("PUT", "/api/device/456/control")
+            .with_status(200)
+            .with_body(r#"{"data": {"locking": 3}}"#)
+            .create();
+        let _pending = mock("GET", "/api/device/456/status")
+            .with_status(200)
+            .with_body(status(0))
+            .create();
+
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().set_lock_mode(&flap(), LockMode::Both)),
+            Err(SurepetError::Unacknowledged(_))
+        ));
+    }
+
+    #[test]
+    fn it_sets_the_curfews_of_a_cat_flap() {
+        let control = mock("PUT", "/api/device/456/control")
+            .match_body(Matcher::Json(json!({
+                "curfew": [
+                    {"enabled": true, "lock_time": "19:00", "unlock_time": "07:00"},
+                    {"enabled": true, "lock_time": "12:00", "unlock_time": "14:00"}
+                ]
+            })))
+            .with_status(200)
+            .with_body(r#"{"data": {}}"#)
+            .create();
+        let _current = mock("GET", "/api/device/456/control")
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": {
+                        "curfew": [
+                            {"enabled": true, "lock_time": "19:00", "unlock_time": "07:00"},
+                            {"enabled": true, "lock_time": "12:00", "unlock_time": "14:00"}
+                        ]
+                    }
+                }
+                "#,
+            )
+            .create();
+        let curfews: Vec<Curfew> = vec![
+            "19:00-07:00".parse().unwrap(),
+            "12:00-14:00".parse().unwrap(),
+        ];
+        let cat_flap = Flap {
+            product: FlapProduct::CatFlap,
+            ..flap()
+        };
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().set_curfews(&cat_flap, &curfews)).unwrap(),
+            curfews
+        );
+        control.assert();
+    }
+
+    #[test]
+    fn it_disables_the_curfew_of_a_pet_door() {
+        let _current = mock("GET", "/api/device/456/control")
+            .with_status(200)
+            .with_body(
+                r#"{"data": {"curfew": {"enabled": true, "lock_time": "19:00", "unlock_time": "07:00"}}}"#,
+            )
+            .create();
+        let control = mock("PUT", "/api/device/456/control")
+            .match_body(Matcher::Json(json!({
+                "curfew": {"enabled": false, "lock_time": "19:00", "unlock_time": "07:00"}
+            })))
+            .with_status(200)
+            .with_body(r#"{"data": {}}"#)
+            .create();
+
+        tokio_test::block_on(authenticated_client().clear_curfews(&flap())).unwrap();
+        control.assert();
+    }
+
+    #[test]
+    fn it_refuses_overlapping_curfews() {
+        let control = mock("PUT", "/api/device/456/control").expect(0).create();
+        let cat_flap = Flap {
+            product: FlapProduct::CatFlap,
+            ..flap()
+        };
+
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().set_curfews(
+                &cat_flap,
+                &[
+                    "19:00-07:00".parse().unwrap(),
+                    "06:00-08:00".parse().unwrap()
+                ]
+            )),
+            Err(SurepetError::InvalidCurfew(_))
+        ));
+        control.assert();
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::SurepetClient;
+use crate::entities::household::Household;
+use crate::error::{Result, SurepetError};
+
+pub(crate) const HOUSEHOLDS_PATH: &str = "/api/household";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HouseholdsResponse {
+    data: Vec<HouseholdData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HouseholdData {
+    id: u64,
+    name: String,
+}
+
+impl SurepetClient {
+    /// Retrieve the households the account belongs to.
+    pub async fn households(&self) -> Result<Vec<Household>> {
+        let parsed: HouseholdsResponse = self.get_resources(HOUSEHOLDS_PATH).await?;
+
+        Ok(parsed
+            .data
+            .into_iter()
+            .map(|household| Household {
+                id: household.id,
+                name: household.name,
+            })
+            .collect())
+    }
+
+    /// Find a household by its ID, or its name ignoring case.
+    pub async fn household(&self, name_or_id: &str) -> Result<Household> {
+        self.households()
+            .await?
+            .into_iter()
+            .find(|household| {
+                household.id.to_string() == name_or_id
+                    || household.name.eq_ignore_ascii_case(name_or_id)
+            })
+            .ok_or_else(|| SurepetError::NotFound(format!("household `{}`", name_or_id)))
+    }
+
+    /// IDs of the household the client deals with, or of every household of the account.
+    pub(crate) async fn household_ids(&self) -> Result<Vec<u64>> {
+        if let Some(household) = self.household {
+            return Ok(vec![household]);
+        }
+        Ok(self
+            .households()
+            .await?
+            .iter()
+            .map(|household| household.id)
+            .collect())
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::{authenticated_client, mocked_households};
+
+    #[test]
+    fn it_returns_households() {
+        let _m = mocked_households();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().households()).unwrap(),
+            vec![
+                Household {
+                    id: 42,
+                    name: "Home".to_string(),
+                },
+                Household {
+                    id: 43,
+                    name: "Office".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_a_household_by_name_or_id() {
+        let _m = mocked_households();
+        let client = authenticated_client();
+
+        assert_eq!(
+            tokio_test::block_on(client.household("office")).unwrap().id,
+            43
+        );
+        assert_eq!(
+            tokio_test::block_on(client.household("42")).unwrap().name,
+            "Home"
+        );
+        assert!(matches!(
+            tokio_test::block_on(client.household("Cottage")),
+            Err(SurepetError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn it_defaults_to_every_household() {
+        let _m = mocked_households();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().household_ids()).unwrap(),
+            vec![42, 43]
+        );
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().with_household(43).household_ids())
+                .unwrap(),
+            vec![43]
+        );
+    }
+}
@@ -1,10 +1,13 @@
+use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env};
+use std::collections::HashMap;
 
-#[cfg(not(test))]
-use crate::api::BASE_URL;
+use crate::api::decode;
+use crate::client::{Credentials, SurepetClient};
+use crate::error::{Result, SurepetError};
 
 const LOGIN_PATH: &str = "/api/auth/login";
+const LOGOUT_PATH: &str = "/api/auth/logout";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct LoginResponse {
@@ -16,45 +19,58 @@ struct LoginData {
     token: String,
 }
 
-/// Get an authentication token.
-///
-/// It expects the following environment variables to be defined:
-///
-/// - `SUREPET_EMAIL`: email of your surepet account
-/// - `SUREPET_PASSWORD`: password of your surepet account
-pub async fn login() -> String {
-    let email = env::var("SUREPET_EMAIL").expect("Please set `SUREPET_EMAIL` env variable");
-    let password =
-        env::var("SUREPET_PASSWORD").expect("Please set `SUREPET_PASSWORD` env variable");
-    let mut payload = HashMap::new();
-    payload.insert("email_address", email);
-    payload.insert("password", password);
-    payload.insert("device_id", ".".to_string());
-
-    #[cfg(not(test))]
-    let url = format!("{}{}", BASE_URL, LOGIN_PATH);
+impl SurepetClient {
+    /// Get an authentication token.
+    ///
+    /// It uses the client credentials, or the environment ones if there are none
+    /// (see [`Credentials::from_env`]).
+    pub async fn login(&self) -> Result<String> {
+        let credentials = self.credentials()?;
+        let mut payload = HashMap::new();
+        payload.insert("email_address", credentials.email);
+        payload.insert("password", credentials.password.resolve()?);
+        payload.insert("device_id", ".".to_string());
 
-    #[cfg(test)]
-    let url = format!("{}{}", &mockito::server_url(), LOGIN_PATH);
-
-    let client = reqwest::Client::builder()
-        .user_agent("surepet-cli")
-        .build()
-        .unwrap();
-    let response = client.post(url).json(&payload).send().await.unwrap();
-
-    match response.status() {
-        reqwest::StatusCode::OK => {
-            match response.json::<LoginResponse>().await {
-                Ok(parsed) => return parsed.data.token,
-                Err(_) => panic!("Hm, the response didn't match the shape we expected."),
-            };
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, LOGIN_PATH))
+            .json(&payload)
+            .send()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => {
+                let parsed: LoginResponse = decode(&response.text().await?)?;
+                Ok(parsed.data.token)
+            }
+            reqwest::StatusCode::UNAUTHORIZED => Err(SurepetError::Unauthorized),
+            status => Err(SurepetError::Http {
+                status,
+                body: response.text().await?,
+            }),
         }
-        reqwest::StatusCode::UNAUTHORIZED => {
-            panic!("Invalid credentials");
+    }
+
+    /// The client credentials, or the environment ones if there are none.
+    pub(crate) fn credentials(&self) -> Result<Credentials> {
+        match &self.credentials {
+            Some(credentials) => Ok(credentials.clone()),
+            None => Credentials::from_env(),
         }
-        _ => {
-            panic!("Uh oh! Something unexpected happened.");
+    }
+
+    /// Ask the API to invalidate a token, so that it can't be used anymore.
+    ///
+    /// A token the API already rejects is considered revoked.
+    pub async fn revoke_token(&self, token: &str) -> Result<()> {
+        let response = self.send(Method::POST, LOGOUT_PATH, None, token).await?;
+
+        match response.status() {
+            StatusCode::OK | StatusCode::NO_CONTENT | StatusCode::UNAUTHORIZED => Ok(()),
+            status => Err(SurepetError::Http {
+                status,
+                body: response.text().await?,
+            }),
         }
     }
 }
@@ -66,65 +82,114 @@ mod tests {
     #[cfg(test)]
     use super::*;
     #[cfg(test)]
-    use mockito::mock;
+    use crate::api::mocked_client;
+    #[cfg(test)]
+    use mockito::{mock, Matcher};
+
+    #[cfg(test)]
+    fn client() -> SurepetClient {
+        mocked_client().with_credentials("some_email@example.com", "password")
+    }
 
     #[test]
     fn it_returns_the_token() {
         let _m = mock("POST", LOGIN_PATH)
+            .match_body(Matcher::PartialJsonString(
+                r#"{"email_address": "some_email@example.com", "password": "password"}"#
+                    .to_string(),
+            ))
             .with_status(200)
             .with_body(r#"{"data": {"token": "some_token"}}"#)
             .create();
-        temp_env::with_vars(
-            [
-                ("SUREPET_EMAIL", Some("some_email@example.com")),
-                ("SUREPET_PASSWORD", Some("password")),
-            ],
-            || {
-                assert_eq!(tokio_test::block_on(login()), "some_token");
-            }
+        assert_eq!(
+            tokio_test::block_on(client().login()).unwrap(),
+            "some_token"
         );
     }
 
     #[test]
-    #[should_panic(expected = "Please set `SUREPET_EMAIL` env variable")]
-    fn it_panics_when_environment_variables_are_missing() {
+    fn it_uses_environment_credentials_by_default() {
+        let _m = mock("POST", LOGIN_PATH)
+            .match_body(Matcher::PartialJsonString(
+                r#"{"email_address": "other_email@example.com"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"data": {"token": "some_token"}}"#)
+            .create();
         temp_env::with_vars(
             [
-                ("SUREPET_EMAIL", None::<String>),
+                ("SUREPET_EMAIL", Some("other_email@example.com")),
+                ("SUREPET_PASSWORD", Some("password")),
             ],
             || {
-                tokio_test::block_on(login());
-            }
+                let client = mocked_client();
+                assert_eq!(tokio_test::block_on(client.login()).unwrap(), "some_token");
+            },
         );
     }
 
     #[test]
-    #[should_panic(expected = "Invalid credentials")]
-    fn it_panics_when_credentials_are_invalid() {
+    fn it_fails_when_environment_variables_are_missing() {
+        temp_env::with_vars([("SUREPET_EMAIL", None::<String>)], || {
+            let client = mocked_client();
+            assert!(matches!(
+                tokio_test::block_on(client.login()),
+                Err(SurepetError::MissingCredentials(variable)) if variable == "SUREPET_EMAIL"
+            ));
+        });
+    }
+
+    #[test]
+    fn it_fails_when_credentials_are_invalid() {
         let _m = mock("POST", LOGIN_PATH).with_status(401).create();
-        temp_env::with_vars(
-            [
-                ("SUREPET_EMAIL", Some("some_email@example.com")),
-                ("SUREPET_PASSWORD", Some("password")),
-            ],
-            || {
-                tokio_test::block_on(login());
-            }
-        );
+        assert!(matches!(
+            tokio_test::block_on(client().login()),
+            Err(SurepetError::Unauthorized)
+        ));
     }
 
     #[test]
-    #[should_panic(expected = "Uh oh! Something unexpected happened.")]
-    fn it_panics_when_response_is_not_handled() {
-        let _m = mock("POST", LOGIN_PATH).with_status(500).create();
-        temp_env::with_vars(
-            [
-                ("SUREPET_EMAIL", Some("some_email@example.com")),
-                ("SUREPET_PASSWORD", Some("password")),
-            ],
-            || {
-                tokio_test::block_on(login());
+    fn it_fails_when_response_is_not_handled() {
+        let _m = mock("POST", LOGIN_PATH)
+            .with_status(500)
+            .with_body("Internal Server Error")
+            .create();
+        match tokio_test::block_on(client().login()) {
+            Err(SurepetError::Http { status, body }) => {
+                assert_eq!(status, reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+                assert_eq!(body, "Internal Server Error");
             }
-        );
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_fails_when_response_has_an_unexpected_shape() {
+        let _m = mock("POST", LOGIN_PATH)
+            .with_status(200)
+            .with_body(r#"{"data": {"token": 42}}"#)
+            .create();
+        match tokio_test::block_on(client().login()) {
+            Err(SurepetError::Decode { path, .. }) => assert_eq!(path, "data.token"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_revokes_the_token() {
+        let m = mock("POST", LOGOUT_PATH)
+            .match_header("authorization", "Bearer some_token")
+            .with_status(200)
+            .create();
+
+        tokio_test::block_on(mocked_client().revoke_token("some_token")).unwrap();
+        m.assert();
+    }
+
+    #[test]
+    fn it_ignores_tokens_already_revoked() {
+        let _m = mock("POST", LOGOUT_PATH).with_status(401).create();
+
+        assert!(tokio_test::block_on(mocked_client().revoke_token("expired_token")).is_ok());
     }
 }
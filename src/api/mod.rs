@@ -1,48 +1,381 @@
-use reqwest::Response;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::client::{RetryPolicy, SurepetClient};
+use crate::error::{Result, SurepetError};
+use crate::utils::authentication::{forget_token, read_token, store_token, StoredToken};
+
+pub mod account;
 pub mod devices;
+pub mod flaps;
+pub mod households;
 pub mod login;
 pub mod pets;
+pub mod status;
+pub mod timeline;
 
-#[cfg(not(test))]
-/// The base url of surepet API.
-const BASE_URL: &str = "https://app.api.surehub.io";
+impl SurepetClient {
+    /// Token to authenticate with: in memory, from the cache file, or from the API.
+    async fn bearer_token(&self) -> Result<String> {
+        if let Some(token) = self.token.lock().unwrap().clone() {
+            return Ok(token);
+        }
+        if let Some(token_path) = &self.token_cache {
+            // A token issued by another API would be rejected anyway.
+            if let Some(stored) =
+                read_token(token_path)?.filter(|stored| stored.base_url == self.base_url)
+            {
+                *self.token.lock().unwrap() = Some(stored.token.clone());
+                return Ok(stored.token);
+            }
+        }
+        self.refresh_token().await
+    }
 
-#[cfg(not(test))]
-use crate::utils::authentication::bearer_token;
+    /// Forget the current token and query API to get a new one.
+    ///
+    /// Used when the API rejects the token we have (it expired, or was revoked).
+    async fn refresh_token(&self) -> Result<String> {
+        *self.token.lock().unwrap() = None;
+        if let Some(token_path) = &self.token_cache {
+            forget_token(token_path)?;
+        }
 
-/// Make an authenticated HTTP GET to the API.
-async fn get_resources(path: &str) -> Response {
-    #[cfg(not(test))]
-    let bearer_token = bearer_token().await;
+        let token = self.login().await?;
 
-    #[cfg(test)]
-    let bearer_token = "some_token";
+        if let Some(token_path) = &self.token_cache {
+            let email = self.credentials().ok().map(|credentials| credentials.email);
+            store_token(
+                token_path,
+                &StoredToken::new(token.clone(), email, self.base_url.clone()),
+            )?;
+        }
+        *self.token.lock().unwrap() = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Make an authenticated HTTP GET to the API and decode its body.
+    pub(crate) async fn get_resources<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.send_resources(Method::GET, path, None).await
+    }
+
+    /// Make an authenticated HTTP POST to the API and decode its body.
+    pub(crate) async fn post_resources<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        payload: &Value,
+    ) -> Result<T> {
+        self.send_resources(Method::POST, path, Some(payload)).await
+    }
+
+    /// Make an authenticated HTTP PUT to the API and decode its body.
+    pub(crate) async fn put_resources<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        payload: &Value,
+    ) -> Result<T> {
+        self.send_resources(Method::PUT, path, Some(payload)).await
+    }
+
+    /// Make an authenticated HTTP request to the API and decode its body.
+    ///
+    /// When the API rejects the token, a new one is requested and the call is retried once.
+    async fn send_resources<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&Value>,
+    ) -> Result<T> {
+        let mut response = self
+            .send_with_retries(method.clone(), path, payload, &self.bearer_token().await?)
+            .await?;
+
+        if response.status() == StatusCode::UNAUTHORIZED {
+            response = self
+                .send_with_retries(method, path, payload, &self.refresh_token().await?)
+                .await?;
+        }
+
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => decode(&response.text().await?),
+            StatusCode::UNAUTHORIZED => Err(SurepetError::Unauthorized),
+            status => Err(SurepetError::Http {
+                status,
+                body: response.text().await?,
+            }),
+        }
+    }
+
+    /// Make an HTTP request to the API with the given token, retrying GET requests that failed for
+    /// a reason that may not last, as the retry policy says.
+    async fn send_with_retries(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&Value>,
+        bearer_token: &str,
+    ) -> Result<Response> {
+        send_retrying(self.retry_policy, method == Method::GET, || {
+            self.request(method.clone(), path, payload, bearer_token)
+        })
+        .await
+    }
+
+    /// Make an HTTP request to the API with the given token.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&Value>,
+        bearer_token: &str,
+    ) -> Result<Response> {
+        Ok(self
+            .request(method, path, payload, bearer_token)
+            .send()
+            .await?)
+    }
+
+    /// An HTTP request to the API with the given token.
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&Value>,
+        bearer_token: &str,
+    ) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("AUTHORIZATION", format!("Bearer {}", bearer_token));
+        match payload {
+            Some(payload) => request.json(payload),
+            None => request,
+        }
+    }
+}
+
+/// Send the request built by `request`, and when `retry` allows it, send it again as the policy
+/// says while it fails for a reason that may not last: network errors, too many requests, or
+/// server errors.
+///
+/// The response of the last attempt is returned, whatever its status.
+pub(crate) async fn send_retrying(
+    policy: RetryPolicy,
+    retry: bool,
+    request: impl Fn() -> RequestBuilder,
+) -> Result<Response> {
+    for attempt in 0.. {
+        let result = request().send().await;
+        let transient = match &result {
+            Ok(response) => {
+                response.status() == StatusCode::TOO_MANY_REQUESTS
+                    || response.status().is_server_error()
+            }
+            Err(_) => true,
+        };
+        if !retry || !transient || attempt >= policy.retries {
+            return Ok(result?);
+        }
+
+        let delay = match result.as_ref().ok().and_then(retry_after) {
+            Some(delay) => delay.min(policy.max_delay),
+            None => policy.delay(attempt),
+        };
+        tokio::time::sleep(delay).await;
+    }
+    unreachable!()
+}
 
-    #[cfg(not(test))]
-    let url = format!("{}{}", BASE_URL, path);
+/// How long the API asks to wait before the next request, in seconds or as an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => DateTime::parse_from_rfc2822(value)
+            .ok()?
+            .signed_duration_since(Utc::now())
+            .to_std()
+            .ok(),
+    }
+}
+
+/// Deserialize a JSON body, keeping track of where it failed if it does.
+pub(crate) fn decode<T: DeserializeOwned>(body: &str) -> Result<T> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    Ok(serde_path_to_error::deserialize(deserializer)?)
+}
+
+#[cfg(test)]
+/// A client talking to the mock server.
+pub(crate) fn mocked_client() -> SurepetClient {
+    SurepetClient::new()
+        .unwrap()
+        .with_base_url(mockito::server_url())
+        .with_retry_policy(RetryPolicy {
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            ..Default::default()
+        })
+}
+
+#[cfg(test)]
+/// A client already holding `some_token`, so that no login is needed.
+pub(crate) fn authenticated_client() -> SurepetClient {
+    mocked_client().with_token("some_token")
+}
+
+#[cfg(test)]
+/// The mock server answering with the `Home` (42) and `Office` (43) households.
+pub(crate) fn mocked_households() -> mockito::Mock {
+    mockito::mock("GET", households::HOUSEHOLDS_PATH)
+        .with_status(200)
+        .with_body(r#"{"data": [{"id": 42, "name": "Home"}, {"id": 43, "name": "Office"}]}"#)
+        .create()
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use mockito::mock;
 
     #[cfg(test)]
-    let url = format!("{}{}", &mockito::server_url(), path);
+    const SOME_PATH: &str = "/api/some_resource";
 
-    let client = reqwest::Client::builder()
-        .user_agent("surepet-cli")
-        .build()
+    #[test]
+    fn it_refreshes_the_token_when_it_expired() {
+        let _expired = mock("GET", SOME_PATH)
+            .match_header("authorization", "Bearer expired_token")
+            .with_status(401)
+            .create();
+        let _refreshed = mock("GET", SOME_PATH)
+            .match_header("authorization", "Bearer new_token")
+            .with_status(200)
+            .with_body(r#"{"data": 42}"#)
+            .create();
+        let login_mock = mock("POST", "/api/auth/login")
+            .with_status(200)
+            .with_body(r#"{"data": {"token": "new_token"}}"#)
+            .expect(1)
+            .create();
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join("token");
+        let client = mocked_client()
+            .with_credentials("some_email@example.com", "password")
+            .with_token_cache(&token_path);
+        store_token(
+            &token_path,
+            &StoredToken::new("expired_token", None, client.base_url()),
+        )
         .unwrap();
-    let response = client
-        .get(url)
-        .header("AUTHORIZATION", format!("Bearer {}", bearer_token))
-        .send()
-        .await
+
+        let body: serde_json::Value =
+            tokio_test::block_on(client.get_resources(SOME_PATH)).unwrap();
+
+        assert_eq!(body, serde_json::json!({"data": 42}));
+        login_mock.assert();
+        let stored = read_token(&token_path).unwrap().unwrap();
+        assert_eq!(stored.token, "new_token");
+        assert_eq!(stored.email, Some("some_email@example.com".to_string()));
+    }
+
+    #[test]
+    fn it_ignores_tokens_of_another_api() {
+        let _resource = mock("GET", SOME_PATH)
+            .match_header("authorization", "Bearer new_token")
+            .with_status(200)
+            .with_body(r#"{"data": 42}"#)
+            .create();
+        let login_mock = mock("POST", "/api/auth/login")
+            .with_status(200)
+            .with_body(r#"{"data": {"token": "new_token"}}"#)
+            .expect(1)
+            .create();
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join("token");
+        store_token(
+            &token_path,
+            &StoredToken::new("other_token", None, "https://app.api.surehub.io"),
+        )
         .unwrap();
+        let client = mocked_client()
+            .with_credentials("some_email@example.com", "password")
+            .with_token_cache(&token_path);
 
-    match response.status() {
-        reqwest::StatusCode::OK => return response,
-        reqwest::StatusCode::UNAUTHORIZED => {
-            panic!("Need to grab a new token");
-        }
-        _ => {
-            panic!("Uh oh! Something unexpected happened.");
+        let _: serde_json::Value = tokio_test::block_on(client.get_resources(SOME_PATH)).unwrap();
+
+        login_mock.assert();
+    }
+
+    #[test]
+    fn it_retries_only_once() {
+        let resource_mock = mock("GET", SOME_PATH).with_status(401).expect(2).create();
+        let login_mock = mock("POST", "/api/auth/login")
+            .with_status(200)
+            .with_body(r#"{"data": {"token": "new_token"}}"#)
+            .expect(1)
+            .create();
+        let client = mocked_client()
+            .with_credentials("some_email@example.com", "password")
+            .with_token("expired_token");
+
+        assert!(matches!(
+            tokio_test::block_on(client.get_resources::<serde_json::Value>(SOME_PATH)),
+            Err(SurepetError::Unauthorized)
+        ));
+        resource_mock.assert();
+        login_mock.assert();
+    }
+
+    #[test]
+    fn it_retries_when_the_api_is_unavailable() {
+        let _unavailable = mock("GET", SOME_PATH)
+            .with_status(503)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create();
+        let _available = mock("GET", SOME_PATH)
+            .with_status(200)
+            .with_body(r#"{"data": 42}"#)
+            .expect(1)
+            .create();
+
+        let body: serde_json::Value =
+            tokio_test::block_on(authenticated_client().get_resources(SOME_PATH)).unwrap();
+
+        assert_eq!(body, serde_json::json!({"data": 42}));
+    }
+
+    #[test]
+    fn it_gives_up_after_the_last_retry() {
+        let resource_mock = mock("GET", SOME_PATH).with_status(429).expect(4).create();
+
+        match tokio_test::block_on(
+            authenticated_client().get_resources::<serde_json::Value>(SOME_PATH),
+        ) {
+            Err(SurepetError::Http { status, .. }) => {
+                assert_eq!(status, StatusCode::TOO_MANY_REQUESTS)
+            }
+            other => panic!("Unexpected result: {:?}", other),
         }
+        resource_mock.assert();
+    }
+
+    #[test]
+    fn it_does_not_retry_other_requests() {
+        let resource_mock = mock("POST", SOME_PATH).with_status(503).expect(1).create();
+
+        assert!(tokio_test::block_on(
+            authenticated_client()
+                .post_resources::<serde_json::Value>(SOME_PATH, &serde_json::json!({}))
+        )
+        .is_err());
+        resource_mock.assert();
     }
 }
@@ -1,18 +1,27 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use crate::api::get_resources;
-use crate::entities::pet::Pet;
+use crate::client::SurepetClient;
+use crate::entities::pet::{Pet, PetPosition};
+use crate::error::{Result, SurepetError};
 
-const PETS_PATH: &str = "/api/pet?with=position";
+pub(crate) const PETS_PATH: &str = "/api/pet?with=position";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct PetsResponse {
     data: Vec<PetData>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+struct PetPositionResponse {
+    data: PetPositionData,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct PetData {
+    id: u64,
+    household_id: u64,
     name: String,
     position: PetPositionData,
 }
@@ -20,26 +29,83 @@ struct PetData {
 #[derive(Serialize, Deserialize, Debug)]
 struct PetPositionData {
     since: String,
-    r#where: u8,
+    r#where: PetPosition,
 }
 
-/// Retrieve pets from api and convert them to the proper struct.
-pub async fn pets() -> Vec<Pet> {
-    let mut pets: Vec<Pet> = vec![];
+impl SurepetClient {
+    /// Retrieve pets from api and convert them to the proper struct.
+    pub async fn pets(&self) -> Result<Vec<Pet>> {
+        let parsed: PetsResponse = self.get_resources(PETS_PATH).await?;
 
-    match get_resources(PETS_PATH).await.json::<PetsResponse>().await {
-        Ok(parsed) => {
-            for pet in parsed.data {
-                pets.push(Pet {
+        parsed
+            .data
+            .into_iter()
+            .enumerate()
+            .filter(|(_, pet)| self.in_household(pet.household_id))
+            .map(|(index, pet)| {
+                let position_since = pet
+                    .position
+                    .since
+                    .parse::<DateTime<FixedOffset>>()
+                    .map_err(|error| SurepetError::Decode {
+                        path: format!("data[{}].position.since", index),
+                        message: error.to_string(),
+                    })?;
+
+                Ok(Pet {
+                    id: pet.id,
+                    household_id: pet.household_id,
                     name: pet.name,
                     position: pet.position.r#where,
-                    position_since: pet.position.since.parse::<DateTime<FixedOffset>>().unwrap(),
-                });
-            }
-        }
-        Err(_) => panic!("Hm, the response didn't match the shape we expected."),
-    };
-    return pets;
+                    position_since,
+                })
+            })
+            .collect()
+    }
+
+    /// Find a pet by its name, ignoring case.
+    pub async fn pet(&self, name: &str) -> Result<Pet> {
+        self.pets()
+            .await?
+            .into_iter()
+            .find(|pet| pet.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SurepetError::NotFound(format!("pet `{}`", name)))
+    }
+
+    /// Record that a pet went inside or outside, e.g. when the flap missed a passage.
+    ///
+    /// Returns the pet with the position acknowledged by the API.
+    pub async fn set_position(
+        &self,
+        pet: &Pet,
+        position: PetPosition,
+        since: DateTime<FixedOffset>,
+    ) -> Result<Pet> {
+        let parsed: PetPositionResponse = self
+            .post_resources(
+                &format!("/api/pet/{}/position", pet.id),
+                &json!({ "where": position.to_api(), "since": since.to_rfc3339() }),
+            )
+            .await?;
+
+        let position_since =
+            parsed
+                .data
+                .since
+                .parse::<DateTime<FixedOffset>>()
+                .map_err(|error| SurepetError::Decode {
+                    path: "data.since".to_string(),
+                    message: error.to_string(),
+                })?;
+
+        Ok(Pet {
+            id: pet.id,
+            household_id: pet.household_id,
+            name: pet.name.clone(),
+            position: parsed.data.r#where,
+            position_since,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -49,7 +115,9 @@ mod tests {
     #[cfg(test)]
     use super::*;
     #[cfg(test)]
-    use mockito::mock;
+    use crate::api::authenticated_client;
+    #[cfg(test)]
+    use mockito::{mock, Matcher};
 
     #[test]
     fn it_returns_pet_information() {
@@ -60,6 +128,8 @@ mod tests {
                 {
                     "data": [
                         {
+                            "id": 789,
+                            "household_id": 42,
                             "name": "Arlene",
                             "position": {
                                 "where": 1,
@@ -67,6 +137,8 @@ mod tests {
                             }
                         },
                         {
+                            "id": 790,
+                            "household_id": 42,
                             "name": "Garfield",
                             "position": {
                                 "where": 2,
@@ -79,18 +151,22 @@ mod tests {
             )
             .create();
         assert_eq!(
-            tokio_test::block_on(pets()),
+            tokio_test::block_on(authenticated_client().pets()).unwrap(),
             vec![
                 Pet {
+                    id: 789,
+                    household_id: 42,
                     name: "Arlene".to_string(),
-                    position: 1,
+                    position: PetPosition::Inside,
                     position_since: "2023-02-05T14:12:57+00:00"
                         .parse::<DateTime<FixedOffset>>()
                         .unwrap()
                 },
                 Pet {
+                    id: 790,
+                    household_id: 42,
                     name: "Garfield".to_string(),
-                    position: 2,
+                    position: PetPosition::Outside,
                     position_since: "2023-02-05T16:09:52+00:00"
                         .parse::<DateTime<FixedOffset>>()
                         .unwrap()
@@ -100,9 +176,134 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Uh oh! Something unexpected happened.")]
-    fn it_panics_when_response_is_not_handled() {
+    fn it_returns_pets_of_the_selected_household() {
+        let _m = mock("GET", PETS_PATH)
+            .with_status(200)
+            .with_body(
+                r#"{"data": [
+                    {"id": 789, "household_id": 42, "name": "Arlene", "position": {"where": 1, "since": "2023-02-05T14:12:57+00:00"}},
+                    {"id": 790, "household_id": 43, "name": "Garfield", "position": {"where": 1, "since": "2023-02-05T14:12:57+00:00"}}
+                ]}"#,
+            )
+            .create();
+
+        let pets = tokio_test::block_on(authenticated_client().with_household(43).pets()).unwrap();
+
+        assert_eq!(
+            pets.iter()
+                .map(|pet| pet.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Garfield"]
+        );
+    }
+
+    #[test]
+    fn it_keeps_pets_with_an_unknown_position() {
+        let _m = mock("GET", PETS_PATH)
+            .with_status(200)
+            .with_body(
+                r#"{"data": [{"id": 789, "household_id": 42, "name": "Arlene", "position": {"where": 0, "since": "2023-02-05T14:12:57+00:00"}}]}"#,
+            )
+            .create();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().pets()).unwrap()[0].position,
+            PetPosition::Unknown(0)
+        );
+    }
+
+    #[test]
+    fn it_fails_when_position_date_is_invalid() {
+        let _m = mock("GET", PETS_PATH)
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "data": [
+                        {
+                            "id": 789,
+                            "household_id": 42,
+                            "name": "Arlene",
+                            "position": {
+                                "where": 1,
+                                "since": "yesterday"
+                            }
+                        }
+                    ]
+                }
+                "#,
+            )
+            .create();
+
+        match tokio_test::block_on(authenticated_client().pets()) {
+            Err(SurepetError::Decode { path, .. }) => assert_eq!(path, "data[0].position.since"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_fails_when_response_is_not_handled() {
         let _m = mock("GET", PETS_PATH).with_status(500).create();
-        tokio_test::block_on(pets());
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().pets()),
+            Err(SurepetError::Http { .. })
+        ));
+    }
+
+    #[test]
+    fn it_finds_a_pet_by_name() {
+        let _m = mock("GET", PETS_PATH)
+            .with_status(200)
+            .with_body(
+                r#"{"data": [{"id": 789, "household_id": 42, "name": "Arlene", "position": {"where": 1, "since": "2023-02-05T14:12:57+00:00"}}]}"#,
+            )
+            .create();
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().pet("arlene"))
+                .unwrap()
+                .id,
+            789
+        );
+        assert!(matches!(
+            tokio_test::block_on(authenticated_client().pet("Garfield")),
+            Err(SurepetError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn it_sets_the_position_of_a_pet() {
+        let m = mock("POST", "/api/pet/789/position")
+            .match_body(Matcher::Json(json!({
+                "where": 2,
+                "since": "2023-02-05T18:30:00+01:00"
+            })))
+            .with_status(200)
+            .with_body(
+                r#"{"data": {"tag_id": 12, "where": 2, "since": "2023-02-05T17:30:00+00:00"}}"#,
+            )
+            .create();
+        let pet = Pet {
+            id: 789,
+            household_id: 42,
+            name: "Arlene".to_string(),
+            position: PetPosition::Inside,
+            position_since: "2023-02-05T14:12:57+00:00".parse().unwrap(),
+        };
+
+        assert_eq!(
+            tokio_test::block_on(authenticated_client().set_position(
+                &pet,
+                PetPosition::Outside,
+                "2023-02-05T18:30:00+01:00".parse().unwrap()
+            ))
+            .unwrap(),
+            Pet {
+                position: PetPosition::Outside,
+                position_since: "2023-02-05T17:30:00+00:00".parse().unwrap(),
+                ..pet
+            }
+        );
+        m.assert();
     }
 }
@@ -0,0 +1,64 @@
+use crate::client::SurepetClient;
+use crate::entities::status::Status;
+use crate::error::Result;
+
+impl SurepetClient {
+    /// Retrieve devices and pets at once.
+    ///
+    /// The token is obtained first, so that both requests share it, then they are sent
+    /// concurrently.
+    pub async fn status(&self) -> Result<Status> {
+        self.bearer_token().await?;
+        let ((devices, flaps), pets) = tokio::try_join!(self.devices_and_flaps(), self.pets())?;
+
+        Ok(Status {
+            devices,
+            flaps,
+            pets,
+        })
+    }
+}
+
+#[cfg(test)]
+use mockito;
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::{devices::DEVICES_PATH, mocked_client, pets::PETS_PATH};
+    #[cfg(test)]
+    use mockito::mock;
+
+    #[test]
+    fn it_returns_devices_then_pets_with_a_single_login() {
+        let login_mock = mock("POST", "/api/auth/login")
+            .with_status(200)
+            .with_body(r#"{"data": {"token": "some_token"}}"#)
+            .expect(1)
+            .create();
+        let _devices = mock("GET", DEVICES_PATH)
+            .match_header("authorization", "Bearer some_token")
+            .with_status(200)
+            .with_body(
+                r#"{"data": [{"id": 123, "household_id": 42, "product_id": 1, "name": "Hub",
+                              "status": {"online": true}}]}"#,
+            )
+            .create();
+        let _pets = mock("GET", PETS_PATH)
+            .match_header("authorization", "Bearer some_token")
+            .with_status(200)
+            .with_body(
+                r#"{"data": [{"id": 789, "household_id": 42, "name": "Arlene",
+                              "position": {"where": 1, "since": "2023-02-05T14:12:57+00:00"}}]}"#,
+            )
+            .create();
+        let client = mocked_client().with_credentials("some_email@example.com", "password");
+
+        let overview = tokio_test::block_on(client.status()).unwrap().overview();
+
+        let kinds: Vec<&str> = overview.iter().map(|entity| entity.record().kind).collect();
+        assert_eq!(kinds, vec!["hub", "pet"]);
+        login_mock.assert();
+    }
+}
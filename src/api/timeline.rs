@@ -0,0 +1,461 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::client::SurepetClient;
+use crate::entities::event::{Event, EventKind};
+use crate::entities::pet::{Pet, PetPosition};
+use crate::error::{Result, SurepetError};
+
+/// How many events to ask for at once.
+const PAGE_SIZE: usize = 50;
+
+// Types of the timeline events we know how to read.
+const MOVEMENT: u16 = 0;
+const LOW_BATTERY: u16 = 1;
+const LOCK_MODE_CHANGED: u16 = 6;
+const USER_UPDATED: u16 = 12;
+const USER_JOINED: u16 = 17;
+const CURFEW_CHANGED: u16 = 20;
+const FEEDING: u16 = 22;
+const DRINKING: u16 = 29;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TimelineResponse {
+    data: Vec<EventData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EventData {
+    id: u64,
+    r#type: u16,
+    created_at: String,
+    #[serde(default)]
+    pets: Vec<NamedData>,
+    #[serde(default)]
+    devices: Vec<NamedData>,
+    #[serde(default)]
+    users: Vec<NamedData>,
+    #[serde(default)]
+    movements: Vec<MovementData>,
+    #[serde(default)]
+    weights: Vec<WeightData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct NamedData {
+    id: u64,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MovementData {
+    direction: PetPosition,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct WeightData {
+    frames: Vec<FrameData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FrameData {
+    change: f64,
+}
+
+impl SurepetClient {
+    /// What happened in the household of the client (or in every one), oldest first.
+    ///
+    /// Pages through the timelines back to `since`, keeping the events of `pet` only when given,
+    /// and the `limit` most recent ones when given.
+    pub async fn timeline(
+        &self,
+        pet: Option<&Pet>,
+        since: DateTime<FixedOffset>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Event>> {
+        let households = match pet {
+            Some(pet) => vec![pet.household_id],
+            None => self.household_ids().await?,
+        };
+        let mut events = vec![];
+        for household in households {
+            events.extend(
+                self.household_timeline(household, pet, since, limit)
+                    .await?,
+            );
+        }
+
+        events.sort_by_key(|event| event.at);
+        if let Some(limit) = limit {
+            events.drain(..events.len().saturating_sub(limit));
+        }
+        Ok(events)
+    }
+
+    /// What happened in a household, most recent first as far as the API goes.
+    async fn household_timeline(
+        &self,
+        household: u64,
+        pet: Option<&Pet>,
+        since: DateTime<FixedOffset>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Event>> {
+        let mut events = vec![];
+
+        for page in 1.. {
+            let parsed: TimelineResponse = self
+                .get_resources(&format!(
+                    "/api/timeline/household/{}?page={}&page_size={}",
+                    household, page, PAGE_SIZE
+                ))
+                .await?;
+            let count = parsed.data.len();
+            let mut reached_since = false;
+
+            for (index, data) in parsed.data.into_iter().enumerate() {
+                let (event, pets) = event(index, data)?;
+                if event.at < since {
+                    reached_since = true;
+                    break;
+                }
+                match pet {
+                    Some(pet) if !pets.contains(&pet.id) => {}
+                    _ => events.push(event),
+                }
+            }
+
+            let reached_limit = limit.is_some_and(|limit| events.len() >= limit);
+            if reached_since || reached_limit || count < PAGE_SIZE {
+                break;
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Convert an event of the API, along with the IDs of the pets involved.
+fn event(index: usize, data: EventData) -> Result<(Event, Vec<u64>)> {
+    let at = data
+        .created_at
+        .parse::<DateTime<FixedOffset>>()
+        .map_err(|error| SurepetError::Decode {
+            path: format!("data[{}].created_at", index),
+            message: error.to_string(),
+        })?
+        .with_timezone(&Local)
+        .into();
+    let pet = name(&data.pets);
+    let device = name(&data.devices);
+    let weight_change = data
+        .weights
+        .iter()
+        .flat_map(|weight| &weight.frames)
+        .map(|frame| frame.change)
+        .sum();
+
+    let kind = match data.r#type {
+        MOVEMENT => match data.movements.first() {
+            Some(movement) => EventKind::Movement {
+                pet,
+                position: movement.direction,
+                device,
+            },
+            None => return Err(missing(index, "movements")),
+        },
+        FEEDING => EventKind::Feeding {
+            pet,
+            device,
+            weight_change,
+        },
+        DRINKING => EventKind::Drinking {
+            pet,
+            device,
+            weight_change,
+        },
+        LOW_BATTERY => EventKind::Device {
+            device,
+            description: "battery is low",
+        },
+        LOCK_MODE_CHANGED => EventKind::Device {
+            device,
+            description: "lock mode changed",
+        },
+        CURFEW_CHANGED => EventKind::Device {
+            device,
+            description: "curfew changed",
+        },
+        USER_JOINED => EventKind::User {
+            user: name(&data.users),
+            description: "joined the household",
+        },
+        USER_UPDATED => EventKind::User {
+            user: name(&data.users),
+            description: "updated their account",
+        },
+        other => EventKind::Other(other),
+    };
+
+    Ok((
+        Event {
+            id: data.id,
+            at,
+            kind,
+        },
+        data.pets.iter().map(|pet| pet.id).collect(),
+    ))
+}
+
+/// Name of the first pet, device or user involved in an event.
+fn name(named: &[NamedData]) -> String {
+    named
+        .first()
+        .map(|named| named.name.clone())
+        .unwrap_or_default()
+}
+
+fn missing(index: usize, field: &str) -> SurepetError {
+    SurepetError::Decode {
+        path: format!("data[{}]", index),
+        message: format!("missing field `{}`", field),
+    }
+}
+
+#[cfg(test)]
+use mockito;
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::{authenticated_client, mocked_households};
+    #[cfg(test)]
+    use mockito::{mock, Matcher, Mock};
+
+    #[cfg(test)]
+    fn page(page: usize, body: &str) -> Mock {
+        household_page(42, page, body)
+    }
+
+    #[cfg(test)]
+    fn household_page(household: u64, page: usize, body: &str) -> Mock {
+        mock(
+            "GET",
+            format!("/api/timeline/household/{}", household).as_str(),
+        )
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("page".to_string(), page.to_string()),
+            Matcher::UrlEncoded("page_size".to_string(), PAGE_SIZE.to_string()),
+        ]))
+        .with_status(200)
+        .with_body(body)
+        .create()
+    }
+
+    #[cfg(test)]
+    const TIMELINE: &str = r#"
+    {
+        "data": [
+            {
+                "id": 3,
+                "type": 22,
+                "created_at": "2023-02-05T16:00:00+00:00",
+                "pets": [{"id": 790, "name": "Arlene"}],
+                "devices": [{"id": 12, "name": "Feeder"}],
+                "weights": [{"frames": [{"index": 0, "change": -5.5}, {"index": 1, "change": -2.0}]}]
+            },
+            {
+                "id": 2,
+                "type": 0,
+                "created_at": "2023-02-05T14:02:00+00:00",
+                "pets": [{"id": 789, "name": "Garfield"}],
+                "devices": [{"id": 456, "name": "Buanderie"}],
+                "movements": [{"direction": 2}]
+            },
+            {
+                "id": 1,
+                "type": 1,
+                "created_at": "2023-02-04T10:00:00+00:00",
+                "devices": [{"id": 456, "name": "Buanderie"}]
+            }
+        ]
+    }
+    "#;
+
+    #[cfg(test)]
+    fn garfield() -> Pet {
+        Pet {
+            id: 789,
+            household_id: 42,
+            name: "Garfield".to_string(),
+            position: PetPosition::Outside,
+            position_since: "2023-02-05T14:02:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn it_returns_events_since_a_date_oldest_first() {
+        let _m = page(1, TIMELINE);
+
+        let events = tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            None,
+            "2023-02-05T00:00:00+00:00".parse().unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    id: 2,
+                    at: "2023-02-05T14:02:00+00:00".parse().unwrap(),
+                    kind: EventKind::Movement {
+                        pet: "Garfield".to_string(),
+                        position: PetPosition::Outside,
+                        device: "Buanderie".to_string(),
+                    },
+                },
+                Event {
+                    id: 3,
+                    at: "2023-02-05T16:00:00+00:00".parse().unwrap(),
+                    kind: EventKind::Feeding {
+                        pet: "Arlene".to_string(),
+                        device: "Feeder".to_string(),
+                        weight_change: -7.5,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_merges_the_timelines_of_every_household() {
+        let _h = mocked_households();
+        let _home = page(1, TIMELINE);
+        let _office = household_page(
+            43,
+            1,
+            r#"{"data": [{"id": 4, "type": 99, "created_at": "2023-02-05T15:00:00+00:00"}]}"#,
+        );
+
+        let events = tokio_test::block_on(authenticated_client().timeline(
+            None,
+            "2023-02-05T00:00:00+00:00".parse().unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id).collect::<Vec<u64>>(),
+            vec![2, 4, 3]
+        );
+    }
+
+    #[test]
+    fn it_filters_events_by_pet() {
+        let _m = page(1, TIMELINE);
+
+        let events = tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            Some(&garfield()),
+            "2023-02-01T00:00:00+00:00".parse().unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id).collect::<Vec<u64>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_most_recent_events() {
+        let _m = page(1, TIMELINE);
+
+        let events = tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            None,
+            "2023-02-01T00:00:00+00:00".parse().unwrap(),
+            Some(2),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id).collect::<Vec<u64>>(),
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn it_sorts_events_by_date() {
+        let _m = page(
+            1,
+            r#"
+            {
+                "data": [
+                    {"id": 1, "type": 99, "created_at": "2023-02-05T13:00:00+00:00"},
+                    {"id": 3, "type": 99, "created_at": "2023-02-05T15:00:00+00:00"},
+                    {"id": 2, "type": 99, "created_at": "2023-02-05T14:00:00+00:00"}
+                ]
+            }
+            "#,
+        );
+
+        let events = tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            None,
+            "2023-02-01T00:00:00+00:00".parse().unwrap(),
+            Some(2),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            events.iter().map(|event| event.id).collect::<Vec<u64>>(),
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn it_pages_through_the_timeline() {
+        let events: Vec<String> = (0..PAGE_SIZE)
+            .map(|index| {
+                format!(
+                    r#"{{"id": {}, "type": 99, "created_at": "2023-02-05T14:00:00+00:00"}}"#,
+                    100 + index
+                )
+            })
+            .collect();
+        let first = page(1, &format!(r#"{{"data": [{}]}}"#, events.join(",")));
+        let second = page(
+            2,
+            r#"{"data": [{"id": 1, "type": 99, "created_at": "2023-02-05T13:00:00+00:00"}]}"#,
+        );
+
+        let events = tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            None,
+            "2023-02-01T00:00:00+00:00".parse().unwrap(),
+            None,
+        ))
+        .unwrap();
+
+        assert_eq!(events.len(), PAGE_SIZE + 1);
+        assert_eq!(events[0].kind, EventKind::Other(99));
+        first.assert();
+        second.assert();
+    }
+
+    #[test]
+    fn it_fails_when_event_date_is_invalid() {
+        let _m = page(
+            1,
+            r#"{"data": [{"id": 1, "type": 1, "created_at": "yesterday"}]}"#,
+        );
+
+        match tokio_test::block_on(authenticated_client().with_household(42).timeline(
+            None,
+            "2023-02-01T00:00:00+00:00".parse().unwrap(),
+            None,
+        )) {
+            Err(SurepetError::Decode { path, .. }) => assert_eq!(path, "data[0].created_at"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
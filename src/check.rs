@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::client::SurepetClient;
+use crate::config::CheckRules;
+use crate::entities::pet::PetPosition;
+use crate::entities::status::Status;
+use crate::error::Result;
+use crate::utils::authentication::write_private;
+use crate::utils::paths::state_dir;
+
+const OFFLINE_FLAPS_PATH: &str = "offline_flaps.json";
+
+/// States of a monitoring plugin, from the best to the worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl Severity {
+    /// Exit code of a Nagios plugin in this state.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Severity::Ok => 0,
+            Severity::Warning => 1,
+            Severity::Critical => 2,
+            Severity::Unknown => 3,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            Severity::Ok => "OK",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+            Severity::Unknown => "UNKNOWN",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Problems found by the rules, and the values they looked at.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    /// Worst first.
+    pub problems: Vec<(Severity, String)>,
+    /// Values in the Nagios performance data format, like `'Buanderie battery'=49.69%;20:;10:;0;100`.
+    pub perfdata: Vec<String>,
+    /// How many devices and pets were checked.
+    pub checked: usize,
+}
+
+impl Report {
+    pub fn severity(&self) -> Severity {
+        self.problems
+            .iter()
+            .map(|(severity, _)| *severity)
+            .max()
+            .unwrap_or(Severity::Ok)
+    }
+
+    fn problem(&mut self, severity: Severity, message: String) {
+        self.problems.push((severity, message));
+    }
+}
+
+impl fmt::Display for Report {
+    /// Output of a Nagios plugin.
+    ///
+    /// Example:
+    /// ```text
+    /// SUREPET WARNING - Garfield is outside for 13h 2m | 'Hub online'=1 'Garfield outside'=46920s;43200
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = if self.problems.is_empty() {
+            format!("{} devices and pets are fine", self.checked)
+        } else {
+            self.problems
+                .iter()
+                .map(|(_, message)| message.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(f, "SUREPET {} - {}", self.severity(), summary)?;
+        if !self.perfdata.is_empty() {
+            write!(f, " | {}", self.perfdata.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Since when flaps have been seen disconnected, by ID.
+pub type OfflineFlaps = BTreeMap<u64, DateTime<Utc>>;
+
+/// Where `check` remembers since when flaps are disconnected, between two runs.
+pub fn offline_flaps_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join(OFFLINE_FLAPS_PATH))
+}
+
+/// Flaps known to be disconnected; none when the file is missing or unreadable.
+pub fn read_offline_flaps(path: &Path) -> OfflineFlaps {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Remember the flaps known to be disconnected, in a file only readable by the current user.
+pub fn store_offline_flaps(path: &Path, offline_flaps: &OfflineFlaps) -> Result<()> {
+    let content = serde_json::to_string(offline_flaps)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+    Ok(write_private(path, &content)?)
+}
+
+/// Fetch pets and devices, and apply the rules to them.
+pub async fn check(
+    client: &SurepetClient,
+    rules: &CheckRules,
+    offline_flaps_path: &Path,
+    now: DateTime<FixedOffset>,
+) -> Result<Report> {
+    let status = client.status().await?;
+
+    let mut offline_flaps = read_offline_flaps(offline_flaps_path);
+    let report = evaluate(rules, &status, &mut offline_flaps, now);
+    store_offline_flaps(offline_flaps_path, &offline_flaps)?;
+    Ok(report)
+}
+
+/// Apply the rules to pets and devices at the given time.
+///
+/// Flaps seen disconnected for the first time are added to `offline_flaps`, and the ones back
+/// online or gone from the account are removed from it. A pet is outside during a curfew when a flap of its household
+/// keeps pets in.
+pub fn evaluate(
+    rules: &CheckRules,
+    status: &Status,
+    offline_flaps: &mut OfflineFlaps,
+    now: DateTime<FixedOffset>,
+) -> Report {
+    let mut report = Report {
+        checked: status.devices.len() + status.pets.len(),
+        ..Report::default()
+    };
+
+    for device in status.devices.iter().map(|device| device.record()) {
+        if let Some(online) = device.online {
+            report.perfdata.push(format!(
+                "{}={}",
+                label(&format!("{} online", device.name)),
+                if online { 1 } else { 0 }
+            ));
+            if device.kind == "hub" && !online && rules.hub_offline {
+                report.problem(Severity::Critical, format!("{} is offline", device.name));
+            }
+        }
+        if let Some(percent) = device.battery_percent {
+            let (warning, critical) = rules.battery_thresholds(&device.name);
+            // With a trailing colon, Nagios ranges alert below the threshold instead of above.
+            report.perfdata.push(format!(
+                "{}={}%;{}:;{}:;0;100",
+                label(&format!("{} battery", device.name)),
+                percent,
+                warning,
+                critical
+            ));
+            if percent < critical {
+                report.problem(
+                    Severity::Critical,
+                    format!("{} battery is at {}%", device.name, percent),
+                );
+            } else if percent < warning {
+                report.problem(
+                    Severity::Warning,
+                    format!("{} battery is at {}%", device.name, percent),
+                );
+            }
+        }
+    }
+
+    offline_flaps.retain(|id, _| status.flaps.iter().any(|flap| flap.id == *id));
+    for flap in &status.flaps {
+        if flap.online {
+            offline_flaps.remove(&flap.id);
+            continue;
+        }
+        let since = *offline_flaps.entry(flap.id).or_insert(now.into());
+        let offline = elapsed(since.into(), now);
+        if offline >= rules.flap_offline {
+            report.problem(
+                Severity::Critical,
+                format!("{} is offline for {}", flap.name, minutes(offline)),
+            );
+        }
+    }
+
+    for pet in &status.pets {
+        let outside = match pet.position {
+            PetPosition::Outside => elapsed(pet.position_since, now),
+            _ => Duration::ZERO,
+        };
+        report.perfdata.push(format!(
+            "{}={}s;{}",
+            label(&format!("{} outside", pet.name)),
+            outside.as_secs(),
+            rules.pet_outside.as_secs()
+        ));
+        if pet.position != PetPosition::Outside {
+            continue;
+        }
+        if outside > rules.pet_outside {
+            report.problem(
+                Severity::Warning,
+                format!("{} is outside for {}", pet.name, minutes(outside)),
+            );
+        }
+        let curfew = status.flaps.iter().find(|flap| {
+            flap.household_id == pet.household_id
+                && flap
+                    .curfews
+                    .iter()
+                    .any(|curfew| curfew.is_active_at(now.time()))
+        });
+        if let Some(flap) = curfew.filter(|_| rules.curfew) {
+            report.problem(
+                Severity::Warning,
+                format!("{} is outside during the curfew of {}", pet.name, flap.name),
+            );
+        }
+    }
+
+    report
+        .problems
+        .sort_by(|(first, _), (second, _)| second.cmp(first));
+    report
+}
+
+/// A label of performance data, quoted, with its quotes doubled.
+fn label(name: &str) -> String {
+    format!("'{}'", name.replace('\'', "''"))
+}
+
+/// Time elapsed between two dates, or zero if the first one is later.
+fn elapsed(since: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> Duration {
+    now.signed_duration_since(since)
+        .to_std()
+        .unwrap_or_default()
+}
+
+/// A duration to the minute, like `13h 2m`.
+fn minutes(duration: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(duration.as_secs() / 60 * 60)).to_string()
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::entities::curfew::Curfew;
+    #[cfg(test)]
+    use crate::entities::flap::Flap;
+    #[cfg(test)]
+    use crate::entities::hub::Hub;
+    #[cfg(test)]
+    use crate::entities::pet::Pet;
+    #[cfg(test)]
+    use crate::entities::test_support;
+    #[cfg(test)]
+    use chrono::NaiveTime;
+
+    #[cfg(test)]
+    fn flap(online: bool, battery_voltage: f64) -> Flap {
+        Flap {
+            online,
+            battery_voltage,
+            curfews: vec![Curfew {
+                enabled: true,
+                lock_time: NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+                unlock_time: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            }],
+            ..test_support::flap()
+        }
+    }
+
+    #[cfg(test)]
+    fn status(hub_online: bool, flap: Flap, position: PetPosition) -> Status {
+        test_support::status_of(
+            Hub {
+                online: hub_online,
+                ..test_support::hub()
+            },
+            flap,
+            vec![Pet {
+                position,
+                position_since: at("06:00"),
+                ..test_support::pet()
+            }],
+        )
+    }
+
+    #[cfg(test)]
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        format!("2023-01-01T{}:00+00:00", time).parse().unwrap()
+    }
+
+    #[test]
+    fn it_reports_nothing_when_everything_is_fine() {
+        let report = evaluate(
+            &CheckRules::default(),
+            &status(true, flap(true, 5.6), PetPosition::Inside),
+            &mut OfflineFlaps::new(),
+            at("12:00"),
+        );
+
+        assert_eq!(report.severity(), Severity::Ok);
+        assert_eq!(
+            report.to_string(),
+            "SUREPET OK - 3 devices and pets are fine | 'Hub online'=1 'Buanderie online'=1 \
+             'Buanderie battery'=50%;20:;10:;0;100 'Garfield outside'=0s;43200"
+        );
+    }
+
+    #[test]
+    fn it_reports_low_batteries() {
+        let mut rules = CheckRules::default();
+
+        let report = evaluate(
+            &rules,
+            &status(true, flap(true, 4.9), PetPosition::Inside),
+            &mut OfflineFlaps::new(),
+            at("12:00"),
+        );
+        assert_eq!(report.severity(), Severity::Critical);
+        assert_eq!(
+            report.problems,
+            vec![(
+                Severity::Critical,
+                "Buanderie battery is at 6.25%".to_string()
+            )]
+        );
+
+        rules.battery_critical = 5.0;
+        let report = evaluate(
+            &rules,
+            &status(true, flap(true, 4.9), PetPosition::Inside),
+            &mut OfflineFlaps::new(),
+            at("12:00"),
+        );
+        assert_eq!(report.severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn it_reports_offline_hubs() {
+        let report = evaluate(
+            &CheckRules::default(),
+            &status(false, flap(true, 5.6), PetPosition::Inside),
+            &mut OfflineFlaps::new(),
+            at("12:00"),
+        );
+
+        assert_eq!(
+            report.problems,
+            vec![(Severity::Critical, "Hub is offline".to_string())]
+        );
+        assert_eq!(report.severity().exit_code(), 2);
+    }
+
+    #[test]
+    fn it_reports_flaps_offline_for_too_long() {
+        let offline = status(true, flap(false, 5.6), PetPosition::Inside);
+        let mut offline_flaps = OfflineFlaps::new();
+        let rules = CheckRules::default();
+
+        let report = evaluate(&rules, &offline, &mut offline_flaps, at("12:00"));
+        assert_eq!(report.severity(), Severity::Ok);
+        assert_eq!(offline_flaps.get(&456), Some(&at("12:00").into()));
+
+        let report = evaluate(&rules, &offline, &mut offline_flaps, at("12:45"));
+        assert_eq!(
+            report.problems,
+            vec![(
+                Severity::Critical,
+                "Buanderie is offline for 45m".to_string()
+            )]
+        );
+
+        evaluate(
+            &rules,
+            &status(true, flap(true, 5.6), PetPosition::Inside),
+            &mut offline_flaps,
+            at("13:00"),
+        );
+        assert!(offline_flaps.is_empty());
+    }
+
+    #[test]
+    fn it_forgets_flaps_gone_from_the_account() {
+        let mut offline_flaps = OfflineFlaps::from([(457, at("11:00").into())]);
+
+        evaluate(
+            &CheckRules::default(),
+            &status(true, flap(false, 5.6), PetPosition::Inside),
+            &mut offline_flaps,
+            at("12:00"),
+        );
+
+        assert_eq!(
+            offline_flaps,
+            OfflineFlaps::from([(456, at("12:00").into())])
+        );
+    }
+
+    #[test]
+    fn it_reports_pets_outside_for_too_long_or_during_curfews() {
+        let status = status(true, flap(true, 5.6), PetPosition::Outside);
+        let rules = CheckRules::default();
+
+        let report = evaluate(&rules, &status, &mut OfflineFlaps::new(), at("12:00"));
+        assert_eq!(report.severity(), Severity::Ok);
+
+        let report = evaluate(&rules, &status, &mut OfflineFlaps::new(), at("19:30"));
+        assert_eq!(
+            report.problems,
+            vec![
+                (
+                    Severity::Warning,
+                    "Garfield is outside for 13h 30m".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "Garfield is outside during the curfew of Buanderie".to_string()
+                ),
+            ]
+        );
+        assert!(report.to_string().starts_with(
+            "SUREPET WARNING - Garfield is outside for 13h 30m, Garfield is outside during"
+        ));
+    }
+
+    #[test]
+    fn it_only_reports_curfews_of_the_household_of_pets() {
+        let mut status = status(true, flap(true, 5.6), PetPosition::Outside);
+        status.pets[0].household_id = 43;
+
+        let report = evaluate(
+            &CheckRules::default(),
+            &status,
+            &mut OfflineFlaps::new(),
+            at("19:30"),
+        );
+
+        assert_eq!(
+            report.problems,
+            vec![(
+                Severity::Warning,
+                "Garfield is outside for 13h 30m".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn it_escapes_quotes_of_perfdata_labels() {
+        let mut status = status(true, flap(true, 5.6), PetPosition::Inside);
+        status.pets[0].name = "Garfield's brother".to_string();
+
+        let report = evaluate(
+            &CheckRules::default(),
+            &status,
+            &mut OfflineFlaps::new(),
+            at("12:00"),
+        );
+
+        assert!(report
+            .to_string()
+            .ends_with(" 'Garfield''s brother outside'=0s;43200"));
+    }
+
+    #[test]
+    fn it_remembers_offline_flaps() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("surepet").join(OFFLINE_FLAPS_PATH);
+        assert!(read_offline_flaps(&path).is_empty());
+
+        let offline_flaps = OfflineFlaps::from([(456, at("12:00").into())]);
+        store_offline_flaps(&path, &offline_flaps).unwrap();
+
+        assert_eq!(read_offline_flaps(&path), offline_flaps);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::{Result, SurepetError};
+
+/// The base url of surepet API.
+pub const BASE_URL: &str = "https://app.api.surehub.io";
+/// How long to wait for a connection to the API by default.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for a response of the API by default.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How to retry GET requests that failed for a reason that may not last: network errors, too
+/// many requests, or server errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times to retry, after the first attempt.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each following one.
+    pub base_delay: Duration,
+    /// Longest wait between two attempts, even when the API asks for more.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the given retry, counted from zero.
+    ///
+    /// The exponential delay is randomly reduced by up to a half, so that clients failing
+    /// together don't retry together.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        delay / 2 + delay.mul_f64(random_fraction() / 2.0)
+    }
+}
+
+/// A number between 0 and 1, random enough for jitter.
+fn random_fraction() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+/// Email and password of a surepet account.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub email: String,
+    pub password: Password,
+}
+
+/// Password of a surepet account, or where to find it.
+///
+/// Commands and environment variables are only looked at when logging in.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Password {
+    /// The password itself.
+    Plain(String),
+    /// A shell command printing the password, like `pass show surepet`.
+    Command(String),
+    /// The name of an environment variable holding the password.
+    Env(String),
+}
+
+impl Password {
+    /// The password itself, running the command or reading the environment variable if needed.
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Password::Plain(password) => Ok(password.clone()),
+            Password::Env(variable) => {
+                env::var(variable).map_err(|_| SurepetError::MissingCredentials(variable.clone()))
+            }
+            Password::Command(command) => {
+                let failure = |message: String| SurepetError::PasswordCommand {
+                    command: command.clone(),
+                    message,
+                };
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .map_err(|error| failure(error.to_string()))?;
+
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(failure(
+                        format!("{} {}", output.status, stderr.trim())
+                            .trim_end()
+                            .to_string(),
+                    ));
+                }
+                let password =
+                    String::from_utf8(output.stdout).map_err(|error| failure(error.to_string()))?;
+                Ok(password.trim_end_matches(['\r', '\n']).to_string())
+            }
+        }
+    }
+}
+
+impl From<&str> for Password {
+    fn from(password: &str) -> Self {
+        Password::Plain(password.to_string())
+    }
+}
+
+impl From<String> for Password {
+    fn from(password: String) -> Self {
+        Password::Plain(password)
+    }
+}
+
+/// Plain passwords are kept out of logs.
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Password::Plain(_) => write!(f, "Plain(***)"),
+            Password::Command(command) => f.debug_tuple("Command").field(command).finish(),
+            Password::Env(variable) => f.debug_tuple("Env").field(variable).finish(),
+        }
+    }
+}
+
+impl Credentials {
+    /// Read credentials from the following environment variables:
+    ///
+    /// - `SUREPET_EMAIL`: email of your surepet account
+    /// - `SUREPET_PASSWORD`: password of your surepet account
+    pub fn from_env() -> Result<Self> {
+        Ok(Credentials {
+            email: env::var("SUREPET_EMAIL")
+                .map_err(|_| SurepetError::MissingCredentials("SUREPET_EMAIL".to_string()))?,
+            password: Password::Plain(
+                env::var("SUREPET_PASSWORD").map_err(|_| {
+                    SurepetError::MissingCredentials("SUREPET_PASSWORD".to_string())
+                })?,
+            ),
+        })
+    }
+}
+
+/// Entry point to the surepet API.
+///
+/// Example:
+/// ```no_run
+/// # async fn run() -> surepet_rs::Result<()> {
+/// use surepet_rs::SurepetClient;
+///
+/// let client = SurepetClient::new()?.with_credentials("me@example.com", "password");
+/// for pet in client.pets().await? {
+///     println!("{} is {}", pet.name, pet.position.name());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SurepetClient {
+    /// Where the API lives.
+    pub(crate) base_url: String,
+    /// Used to get a token whenever we don't have a valid one, read from the environment if unset.
+    pub(crate) credentials: Option<Credentials>,
+    /// Token sent along every request, once known.
+    pub(crate) token: Mutex<Option<String>>,
+    /// File the token is persisted to, so it survives the process.
+    pub(crate) token_cache: Option<PathBuf>,
+    /// Shared between all the requests to reuse connections.
+    pub(crate) http: reqwest::Client,
+    /// How to retry GET requests that failed.
+    pub(crate) retry_policy: RetryPolicy,
+    /// Fail on devices we don't support, instead of listing them as unknown.
+    pub(crate) strict: bool,
+    /// Only deal with the pets and devices of this household, when set.
+    pub(crate) household: Option<u64>,
+}
+
+impl SurepetClient {
+    /// A client for the surepet API, without credentials nor token yet.
+    pub fn new() -> Result<Self> {
+        Ok(SurepetClient {
+            base_url: BASE_URL.to_string(),
+            credentials: None,
+            token: Mutex::new(None),
+            token_cache: None,
+            http: http_client(CONNECT_TIMEOUT, REQUEST_TIMEOUT)?,
+            retry_policy: RetryPolicy::default(),
+            strict: false,
+            household: None,
+        })
+    }
+
+    /// Talk to another server than the official API (a local stand-in, a proxy, …).
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Authenticate with the given account whenever a token is needed.
+    pub fn with_credentials(
+        mut self,
+        email: impl Into<String>,
+        password: impl Into<Password>,
+    ) -> Self {
+        self.credentials = Some(Credentials {
+            email: email.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Use an already known token instead of logging in.
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        *self.token.lock().unwrap() = Some(token.into());
+        self
+    }
+
+    /// Give up on the API after the given times to connect, and to get a response.
+    pub fn with_timeouts(mut self, connect: Duration, request: Duration) -> Result<Self> {
+        self.http = http_client(connect, request)?;
+        Ok(self)
+    }
+
+    /// Retry GET requests that failed according to the given policy.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Fail with [`SurepetError::UnknownProduct`] on devices we don't support.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Read the token from, and persist it to, the given file.
+    pub fn with_token_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.token_cache = Some(path.into());
+        self
+    }
+
+    /// Where the API lives.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Leave out the pets and devices of other households than the given one.
+    pub fn with_household(mut self, household_id: u64) -> Self {
+        self.household = Some(household_id);
+        self
+    }
+
+    /// Whether pets and devices of the given household are dealt with.
+    pub(crate) fn in_household(&self, household_id: u64) -> bool {
+        match self.household {
+            Some(household) => household == household_id,
+            None => true,
+        }
+    }
+}
+
+pub(crate) fn http_client(
+    connect_timeout: Duration,
+    request_timeout: Duration,
+) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent("surepet-cli")
+        .connect_timeout(connect_timeout)
+        .timeout(request_timeout)
+        .build()?)
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_backs_off_exponentially() {
+        let policy = RetryPolicy {
+            retries: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+
+        for (retry, max) in [(0, 1), (1, 2), (2, 4), (3, 5), (4, 5)] {
+            let delay = policy.delay(retry);
+            assert!(delay >= Duration::from_secs(max) / 2, "{:?}", delay);
+            assert!(delay <= Duration::from_secs(max), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn it_strips_the_trailing_slash_of_base_url() {
+        let client = SurepetClient::new()
+            .unwrap()
+            .with_base_url("http://localhost:8080/");
+
+        assert_eq!(client.base_url, "http://localhost:8080");
+    }
+
+    #[test]
+    fn it_reads_credentials_from_environment() {
+        temp_env::with_vars(
+            [
+                ("SUREPET_EMAIL", Some("some_email@example.com")),
+                ("SUREPET_PASSWORD", Some("password")),
+            ],
+            || {
+                let credentials = Credentials::from_env().unwrap();
+                assert_eq!(credentials.email, "some_email@example.com");
+                assert_eq!(credentials.password, Password::from("password"));
+            },
+        );
+    }
+
+    #[test]
+    fn it_fails_when_environment_variables_are_missing() {
+        temp_env::with_vars(
+            [
+                ("SUREPET_EMAIL", Some("some_email@example.com")),
+                ("SUREPET_PASSWORD", None),
+            ],
+            || {
+                assert!(matches!(
+                    Credentials::from_env(),
+                    Err(SurepetError::MissingCredentials(variable)) if variable == "SUREPET_PASSWORD"
+                ));
+            },
+        );
+    }
+
+    #[test]
+    fn it_resolves_passwords() {
+        assert_eq!(Password::from("secret").resolve().unwrap(), "secret");
+        assert_eq!(
+            Password::Command("echo secret".to_string())
+                .resolve()
+                .unwrap(),
+            "secret"
+        );
+        assert!(matches!(
+            Password::Command("exit 1".to_string()).resolve(),
+            Err(SurepetError::PasswordCommand { .. })
+        ));
+        temp_env::with_var("SOME_PASSWORD", Some("secret"), || {
+            assert_eq!(
+                Password::Env("SOME_PASSWORD".to_string())
+                    .resolve()
+                    .unwrap(),
+                "secret"
+            );
+        });
+    }
+}
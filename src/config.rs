@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::client::{Credentials, Password};
+use crate::error::{Result, SurepetError};
+use crate::output::OutputFormat;
+use crate::watch::ChangeKind;
+
+/// Settings of the CLI, read from a TOML file.
+///
+/// Example:
+/// ```toml
+/// base_url = "http://localhost:8080"
+/// default_profile = "home"
+///
+/// [profiles.home]
+/// email = "me@example.com"
+/// password_command = "pass show surepet"
+///
+/// [profiles.sitter]
+/// email = "sitter@example.com"
+/// password_env = "SITTER_PASSWORD"
+/// household = "Home"
+/// output = "table"
+///
+/// [[webhooks]]
+/// url = "https://chat.example.com/hooks/cats"
+/// events = ["pet_moved"]
+/// template = '{"text": "{message}"}'
+/// secret_env = "CHAT_WEBHOOK_SECRET"
+///
+/// [check]
+/// battery_warning = 25
+/// pet_outside = "8h"
+///
+/// [check.batteries.Buanderie]
+/// critical = 15
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Where the API lives, when it's not the official one.
+    pub base_url: Option<String>,
+    /// Profile used when none is asked for.
+    pub default_profile: Option<String>,
+    /// Accounts and their settings, by name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Where `notify` sends changes.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// What `check` reports.
+    #[serde(default)]
+    pub check: CheckRules,
+}
+
+/// An account, and the settings to use along with it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Email of the account; the `SUREPET_EMAIL` and `SUREPET_PASSWORD` environment variables are
+    /// used when unset.
+    pub email: Option<String>,
+    /// Shell command printing the password.
+    pub password_command: Option<String>,
+    /// Environment variable holding the password, `SUREPET_PASSWORD` by default.
+    pub password_env: Option<String>,
+    /// Where the API lives, overriding the global `base_url`.
+    pub base_url: Option<String>,
+    /// Name or ID of the household to deal with.
+    pub household: Option<String>,
+    /// How to print pets and devices.
+    pub output: Option<OutputFormat>,
+}
+
+/// A URL to POST some changes to.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    /// Changes to send, all of them when empty.
+    #[serde(default)]
+    pub events: Vec<ChangeKind>,
+    /// What the body is: JSON, unless it's `text`.
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Body to send instead of the change (its JSON, or its summary as text), with `{event}`,
+    /// `{name}`, `{position}`, `{since}` and `{message}` replaced.
+    pub template: Option<String>,
+    /// Environment variable holding the key to sign bodies with.
+    pub secret_env: Option<String>,
+}
+
+impl Webhook {
+    /// Whether the webhook wants changes of this kind.
+    pub fn wants(&self, kind: ChangeKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// What the body of a webhook is.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// JSON, with the values filled in templates escaped to fit in strings.
+    #[default]
+    Json,
+    /// Plain text, with the values filled in templates as they are.
+    Text,
+}
+
+impl WebhookFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            WebhookFormat::Json => "application/json",
+            WebhookFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// When `check` reports a problem.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckRules {
+    /// Battery percent under which a device is a warning.
+    pub battery_warning: f64,
+    /// Battery percent under which a device is critical.
+    pub battery_critical: f64,
+    /// Thresholds of some devices, by name, overriding the ones above.
+    pub batteries: BTreeMap<String, BatteryThresholds>,
+    /// Whether a disconnected hub is critical.
+    pub hub_offline: bool,
+    /// How long a flap can stay disconnected before it is critical.
+    #[serde(deserialize_with = "duration")]
+    pub flap_offline: Duration,
+    /// How long a pet can stay outside before it is a warning.
+    #[serde(deserialize_with = "duration")]
+    pub pet_outside: Duration,
+    /// Whether a pet outside while a curfew locks a flap is a warning.
+    pub curfew: bool,
+}
+
+impl Default for CheckRules {
+    fn default() -> Self {
+        CheckRules {
+            battery_warning: 20.0,
+            battery_critical: 10.0,
+            batteries: BTreeMap::new(),
+            hub_offline: true,
+            flap_offline: Duration::from_secs(30 * 60),
+            pet_outside: Duration::from_secs(12 * 60 * 60),
+            curfew: true,
+        }
+    }
+}
+
+impl CheckRules {
+    /// Warning and critical battery percents of a device.
+    pub fn battery_thresholds(&self, device: &str) -> (f64, f64) {
+        let thresholds = self.batteries.get(device);
+        (
+            thresholds
+                .and_then(|thresholds| thresholds.warning)
+                .unwrap_or(self.battery_warning),
+            thresholds
+                .and_then(|thresholds| thresholds.critical)
+                .unwrap_or(self.battery_critical),
+        )
+    }
+}
+
+/// Battery percents of a device.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BatteryThresholds {
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+}
+
+/// A duration written like `30m` or `2h 30m`.
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Duration, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(serde::de::Error::custom)
+}
+
+impl Config {
+    /// Read settings from file, or use the default ones if there is no file.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        toml::from_str(&fs::read_to_string(path)?).map_err(|error| SurepetError::Config {
+            path: path.display().to_string(),
+            message: error.message().to_string(),
+        })
+    }
+
+    /// The profile with the given name, or the default one; `None` when there is neither.
+    pub fn profile<'a>(&'a self, name: Option<&'a str>) -> Result<Option<(&'a str, &'a Profile)>> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => Ok(Some((name, profile))),
+                None => Err(SurepetError::NotFound(format!("profile `{}`", name))),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl Profile {
+    /// Credentials of the account, if the profile has an email.
+    pub fn credentials(&self) -> Option<Credentials> {
+        let email = self.email.clone()?;
+        let password = match (&self.password_command, &self.password_env) {
+            (Some(command), _) => Password::Command(command.clone()),
+            (None, Some(variable)) => Password::Env(variable.clone()),
+            (None, None) => Password::Env("SUREPET_PASSWORD".to_string()),
+        };
+        Some(Credentials { email, password })
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    /// The configuration of a file holding `toml`.
+    fn try_load(toml: &str) -> Result<Config> {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config.toml");
+        fs::write(&path, toml).unwrap();
+        Config::load(&path)
+    }
+
+    #[cfg(test)]
+    fn load(toml: &str) -> Config {
+        try_load(toml).unwrap()
+    }
+
+    #[test]
+    fn it_reads_the_base_url() {
+        assert_eq!(
+            load(r#"base_url = "http://localhost:8080""#),
+            Config {
+                base_url: Some("http://localhost:8080".to_string()),
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn it_uses_defaults_when_file_is_missing() {
+        let directory = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            Config::load(&directory.path().join("config.toml")).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn it_fails_when_file_is_invalid() {
+        assert!(matches!(
+            try_load(r#"base_uri = "http://localhost:8080""#),
+            Err(SurepetError::Config { .. })
+        ));
+    }
+
+    #[cfg(test)]
+    const PROFILES: &str = r#"
+default_profile = "home"
+
+[profiles.home]
+email = "me@example.com"
+password_command = "pass show surepet"
+
+[profiles.sitter]
+email = "sitter@example.com"
+password_env = "SITTER_PASSWORD"
+household = "Home"
+output = "table"
+"#;
+
+    #[test]
+    fn it_reads_profiles() {
+        let config = load(PROFILES);
+
+        let (name, profile) = config.profile(Some("sitter")).unwrap().unwrap();
+        assert_eq!(name, "sitter");
+        assert_eq!(profile.household, Some("Home".to_string()));
+        assert_eq!(profile.output, Some(OutputFormat::Table));
+        assert_eq!(
+            profile.credentials().unwrap().password,
+            Password::Env("SITTER_PASSWORD".to_string())
+        );
+    }
+
+    #[test]
+    fn it_uses_the_default_profile() {
+        let config = load(PROFILES);
+
+        let (name, profile) = config.profile(None).unwrap().unwrap();
+        assert_eq!(name, "home");
+        assert_eq!(
+            profile.credentials().unwrap().password,
+            Password::Command("pass show surepet".to_string())
+        );
+        assert!(Config::default().profile(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn it_reads_webhooks() {
+        let config = load(
+            r#"
+[[webhooks]]
+url = "https://chat.example.com/hooks/cats"
+events = ["pet_moved", "low_battery"]
+secret_env = "CHAT_WEBHOOK_SECRET"
+
+[[webhooks]]
+url = "https://example.com/everything"
+format = "text"
+"#,
+        );
+
+        assert_eq!(config.webhooks.len(), 2);
+        assert!(config.webhooks[0].wants(ChangeKind::LowBattery));
+        assert!(!config.webhooks[0].wants(ChangeKind::Offline));
+        assert!(config.webhooks[1].wants(ChangeKind::Offline));
+        assert_eq!(
+            config.webhooks[0].secret_env,
+            Some("CHAT_WEBHOOK_SECRET".to_string())
+        );
+        assert_eq!(config.webhooks[0].format, WebhookFormat::Json);
+        assert_eq!(config.webhooks[1].format, WebhookFormat::Text);
+    }
+
+    #[test]
+    fn it_reads_check_rules() {
+        let rules = load(
+            r#"
+[check]
+battery_warning = 25
+pet_outside = "8h"
+
+[check.batteries.Buanderie]
+critical = 15
+"#,
+        )
+        .check;
+
+        assert_eq!(rules.pet_outside, Duration::from_secs(8 * 60 * 60));
+        assert_eq!(rules.flap_offline, CheckRules::default().flap_offline);
+        assert_eq!(rules.battery_thresholds("Buanderie"), (25.0, 15.0));
+        assert_eq!(rules.battery_thresholds("Cuisine"), (25.0, 10.0));
+    }
+
+    #[test]
+    fn it_fails_when_profile_is_unknown() {
+        assert!(matches!(
+            Config::default().profile(Some("office")),
+            Err(SurepetError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+/// The surepet account the client is logged in with.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Account {
+    /// ID of the user.
+    pub id: u64,
+    pub email: String,
+    /// First and last names.
+    pub name: String,
+}
@@ -0,0 +1,36 @@
+/// When battery is full.
+const BATTERY_VOLTAGE_FULL: f64 = 1.6;
+/// When battery is low.
+const BATTERY_VOLTAGE_LOW: f64 = 1.2;
+/// How many batteries there are in battery powered devices.
+const BATTERIES_COUNT: u8 = 4;
+/// Below this percentage, the battery is shown as low.
+pub(crate) const BATTERY_PERCENT_LOW: f64 = 10.0;
+
+/// Remaining battery percentage level, for devices running on batteries.
+pub trait BatteryPercent {
+    /// Voltage of all batteries, as reported by the API.
+    fn battery_voltage(&self) -> f64;
+
+    fn battery_percent(&self) -> f64 {
+        let voltage_diff = BATTERY_VOLTAGE_FULL - BATTERY_VOLTAGE_LOW;
+        let voltage_per_battery = self.battery_voltage() / BATTERIES_COUNT as f64;
+        let voltage_per_battery_diff = voltage_per_battery - BATTERY_VOLTAGE_LOW;
+
+        (voltage_per_battery_diff / voltage_diff * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Battery percentage with the precision of the summaries.
+    fn rounded_battery_percent(&self) -> f64 {
+        (self.battery_percent() * 100.0).round() / 100.0
+    }
+
+    /// Icon to show in front of an online device.
+    fn battery_icon(&self) -> &'static str {
+        if self.battery_percent() > BATTERY_PERCENT_LOW {
+            "✅"
+        } else {
+            "🪫"
+        }
+    }
+}
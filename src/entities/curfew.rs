@@ -0,0 +1,175 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{NaiveTime, Timelike};
+
+/// Minutes in a day.
+const DAY: u32 = 24 * 60;
+
+/// A window during which a flap keeps pets in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Curfew {
+    pub enabled: bool,
+    /// When the flap locks.
+    pub lock_time: NaiveTime,
+    /// When the flap unlocks, possibly the day after.
+    pub unlock_time: NaiveTime,
+}
+
+impl Curfew {
+    /// Minutes of the day covered by the curfew, split in two when it spans midnight.
+    fn ranges(&self) -> Vec<(u32, u32)> {
+        let lock = minutes(self.lock_time);
+        let unlock = minutes(self.unlock_time);
+
+        if lock < unlock {
+            vec![(lock, unlock)]
+        } else {
+            vec![(lock, DAY), (0, unlock)]
+        }
+    }
+
+    /// Whether both curfews lock the flap at the same time of the day.
+    pub fn overlaps(&self, other: &Curfew) -> bool {
+        self.ranges().iter().any(|(start, end)| {
+            other
+                .ranges()
+                .iter()
+                .any(|(other_start, other_end)| start < other_end && other_start < end)
+        })
+    }
+
+    /// Whether the flap is locked by this curfew at the given time of the day.
+    pub fn is_active_at(&self, time: NaiveTime) -> bool {
+        let time = minutes(time);
+
+        self.enabled
+            && self
+                .ranges()
+                .iter()
+                .any(|(start, end)| *start <= time && time < *end)
+    }
+}
+
+/// Check that curfews can be set on a flap supporting `max` of them.
+pub fn validate(curfews: &[Curfew], max: usize) -> Result<(), String> {
+    if curfews.len() > max {
+        return Err(format!(
+            "this flap supports {} curfew(s), got {}",
+            max,
+            curfews.len()
+        ));
+    }
+    for curfew in curfews {
+        if curfew.lock_time == curfew.unlock_time {
+            return Err(format!("{} locks and unlocks at the same time", curfew));
+        }
+    }
+    for (index, curfew) in curfews.iter().enumerate() {
+        if let Some(other) = curfews[index + 1..]
+            .iter()
+            .find(|other| curfew.overlaps(other))
+        {
+            return Err(format!("{} overlaps {}", curfew, other));
+        }
+    }
+    Ok(())
+}
+
+fn minutes(time: NaiveTime) -> u32 {
+    time.hour() * 60 + time.minute()
+}
+
+impl fmt::Display for Curfew {
+    /// Example:
+    /// ```text
+    /// 19:00-07:00
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.lock_time.format("%H:%M"),
+            self.unlock_time.format("%H:%M")
+        )
+    }
+}
+
+impl FromStr for Curfew {
+    type Err = String;
+
+    /// Parse an enabled curfew written as `19:00-07:00`.
+    fn from_str(window: &str) -> Result<Self, Self::Err> {
+        let (lock_time, unlock_time) = window
+            .split_once('-')
+            .ok_or_else(|| format!("`{}` is not like 19:00-07:00", window))?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("`{}` is not a time like 19:00", time))
+        };
+
+        Ok(Curfew {
+            enabled: true,
+            lock_time: parse(lock_time)?,
+            unlock_time: parse(unlock_time)?,
+        })
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_parses_a_window() {
+        assert_eq!(
+            "19:00-07:30".parse::<Curfew>().unwrap(),
+            Curfew {
+                enabled: true,
+                lock_time: NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+                unlock_time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+            }
+        );
+        assert!("19:00".parse::<Curfew>().is_err());
+        assert!("19:00-25:00".parse::<Curfew>().is_err());
+    }
+
+    #[test]
+    fn it_detects_overlapping_windows() {
+        let night: Curfew = "19:00-07:00".parse().unwrap();
+
+        assert!(night.overlaps(&"06:00-08:00".parse().unwrap()));
+        assert!(night.overlaps(&"22:00-23:00".parse().unwrap()));
+        assert!(!night.overlaps(&"07:00-12:00".parse().unwrap()));
+        assert!(!night.overlaps(&"12:00-19:00".parse().unwrap()));
+    }
+
+    #[test]
+    fn it_validates_curfews() {
+        let night: Curfew = "19:00-07:00".parse().unwrap();
+        let noon: Curfew = "12:00-14:00".parse().unwrap();
+
+        assert_eq!(validate(&[night.clone(), noon.clone()], 4), Ok(()));
+        assert_eq!(
+            validate(&[night.clone(), noon], 1),
+            Err("this flap supports 1 curfew(s), got 2".to_string())
+        );
+        assert_eq!(
+            validate(&[night, "06:00-08:00".parse().unwrap()], 4),
+            Err("19:00-07:00 overlaps 06:00-08:00".to_string())
+        );
+        assert_eq!(
+            validate(&["07:00-07:00".parse().unwrap()], 4),
+            Err("07:00-07:00 locks and unlocks at the same time".to_string())
+        );
+    }
+
+    #[test]
+    fn it_knows_when_it_is_active() {
+        let night: Curfew = "19:00-07:00".parse().unwrap();
+
+        assert!(night.is_active_at(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert!(night.is_active_at(NaiveTime::from_hms_opt(3, 0, 0).unwrap()));
+        assert!(!night.is_active_at(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+    }
+}
@@ -0,0 +1,301 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use super::pet::PetPosition;
+use super::shared::Columns;
+
+/// Something that happened in the household, from its timeline.
+#[derive(Debug, PartialEq)]
+pub struct Event {
+    pub id: u64,
+    /// When it happened.
+    pub at: DateTime<FixedOffset>,
+    pub kind: EventKind,
+}
+
+/// The kinds of events.
+#[derive(Debug, PartialEq)]
+pub enum EventKind {
+    /// A pet went through a flap.
+    Movement {
+        pet: String,
+        position: PetPosition,
+        device: String,
+    },
+    /// A pet ate from a feeder; `weight_change` is the grams left in the bowls minus the grams before.
+    Feeding {
+        pet: String,
+        device: String,
+        weight_change: f64,
+    },
+    /// A pet drank from a water station; `weight_change` is in grams, i.e. milliliters.
+    Drinking {
+        pet: String,
+        device: String,
+        weight_change: f64,
+    },
+    /// Something about a device, like a low battery or a lock mode change.
+    Device {
+        device: String,
+        description: &'static str,
+    },
+    /// Something about a member of the household.
+    User {
+        user: String,
+        description: &'static str,
+    },
+    /// An event we don't know how to read, with the type used by the API.
+    Other(u16),
+}
+
+impl EventKind {
+    /// Name used in machine-readable outputs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Movement { .. } => "movement",
+            EventKind::Feeding { .. } => "feeding",
+            EventKind::Drinking { .. } => "drinking",
+            EventKind::Device { .. } => "device",
+            EventKind::User { .. } => "user",
+            EventKind::Other(_) => "other",
+        }
+    }
+}
+
+/// What happened, with stable field names.
+///
+/// Fields that don't apply to an event (e.g. the position for a meal) are left out of JSON and
+/// YAML, and empty in CSV, whose columns are always the ones of [`EventRecord::COLUMNS`].
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct EventRecord {
+    /// RFC 3339 date of the event.
+    pub time: String,
+    /// What the event is about: `movement`, `feeding`, `drinking`, `device`, `user` or `other`.
+    pub event: &'static str,
+    /// Pet, device or member of the household the event is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Where a pet went: `inside`, `outside` or `unknown`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<PetPosition>,
+    /// Device a pet went through, ate or drank from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Grams eaten or drunk, as a negative number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight_change: Option<f64>,
+    /// What happened to a device or a member of the household.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Columns for EventRecord {
+    /// New fields are only ever added at the end, so that scripts reading CSV keep working.
+    const COLUMNS: &'static [&'static str] = &[
+        "time",
+        "event",
+        "name",
+        "position",
+        "device",
+        "weight_change",
+        "description",
+    ];
+}
+
+impl Event {
+    /// Summary of an event.
+    ///
+    /// Example:
+    /// ```text
+    /// 2023-02-05 14:02 🏡 Garfield went outside through Buanderie
+    /// ```
+    pub fn information(&self) -> String {
+        let summary = match &self.kind {
+            EventKind::Movement {
+                pet,
+                position: PetPosition::Unknown(position),
+                device,
+            } => format!(
+                "❓ {} went through {} to an unknown position ({})",
+                pet, device, position
+            ),
+            EventKind::Movement {
+                pet,
+                position,
+                device,
+            } => format!(
+                "{} {} went {} through {}",
+                if *position == PetPosition::Inside {
+                    "🏠"
+                } else {
+                    "🏡"
+                },
+                pet,
+                position.name(),
+                device
+            ),
+            EventKind::Feeding {
+                pet,
+                device,
+                weight_change,
+            } => format!("🍽️ {} ate {}g from {}", pet, -weight_change, device),
+            EventKind::Drinking {
+                pet,
+                device,
+                weight_change,
+            } => format!("💧 {} drank {}ml from {}", pet, -weight_change, device),
+            EventKind::Device {
+                device,
+                description,
+            } => format!("🔧 {}: {}", device, description),
+            EventKind::User { user, description } => format!("👤 {} {}", user, description),
+            EventKind::Other(r#type) => format!("❓ Unsupported event (type {})", r#type),
+        };
+        format!("{} {}", self.at.format("%Y-%m-%d %H:%M"), summary)
+    }
+
+    /// Flat representation, used for machine-readable outputs.
+    pub fn record(&self) -> EventRecord {
+        let record = EventRecord {
+            time: self.at.to_rfc3339(),
+            event: self.kind.name(),
+            ..EventRecord::default()
+        };
+
+        match &self.kind {
+            EventKind::Movement {
+                pet,
+                position,
+                device,
+            } => EventRecord {
+                name: Some(pet.clone()),
+                position: Some(*position),
+                device: Some(device.clone()),
+                ..record
+            },
+            EventKind::Feeding {
+                pet,
+                device,
+                weight_change,
+            }
+            | EventKind::Drinking {
+                pet,
+                device,
+                weight_change,
+            } => EventRecord {
+                name: Some(pet.clone()),
+                device: Some(device.clone()),
+                weight_change: Some(*weight_change),
+                ..record
+            },
+            EventKind::Device {
+                device,
+                description,
+            } => EventRecord {
+                name: Some(device.clone()),
+                description: Some(description.to_string()),
+                ..record
+            },
+            EventKind::User { user, description } => EventRecord {
+                name: Some(user.clone()),
+                description: Some(description.to_string()),
+                ..record
+            },
+            EventKind::Other(_) => record,
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    fn event(kind: EventKind) -> Event {
+        Event {
+            id: 1,
+            at: "2023-02-05T14:02:00+01:00".parse().unwrap(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn it_formats_information_of_a_movement() {
+        assert_eq!(
+            event(EventKind::Movement {
+                pet: "Garfield".to_string(),
+                position: PetPosition::Outside,
+                device: "Buanderie".to_string(),
+            })
+            .information(),
+            "2023-02-05 14:02 🏡 Garfield went outside through Buanderie"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_of_a_meal() {
+        assert_eq!(
+            event(EventKind::Feeding {
+                pet: "Garfield".to_string(),
+                device: "Feeder".to_string(),
+                weight_change: -12.5,
+            })
+            .information(),
+            "2023-02-05 14:02 🍽️ Garfield ate 12.5g from Feeder"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_of_an_unsupported_event() {
+        assert_eq!(
+            event(EventKind::Other(42)).information(),
+            "2023-02-05 14:02 ❓ Unsupported event (type 42)"
+        );
+    }
+
+    #[test]
+    fn it_builds_a_record() {
+        assert_eq!(
+            event(EventKind::Drinking {
+                pet: "Garfield".to_string(),
+                device: "Felaqua".to_string(),
+                weight_change: -25.0,
+            })
+            .record(),
+            EventRecord {
+                time: "2023-02-05T14:02:00+01:00".to_string(),
+                event: "drinking",
+                name: Some("Garfield".to_string()),
+                position: None,
+                device: Some("Felaqua".to_string()),
+                weight_change: Some(-25.0),
+                description: None,
+            }
+        );
+    }
+
+    #[test]
+    fn it_has_a_column_per_field() {
+        let record = EventRecord {
+            time: "2023-02-05T14:02:00+01:00".to_string(),
+            event: "movement",
+            name: Some("Garfield".to_string()),
+            position: Some(PetPosition::Outside),
+            device: Some("Buanderie".to_string()),
+            weight_change: Some(-25.0),
+            description: Some("Something happened".to_string()),
+        };
+        let serialized = serde_json::to_value(record).unwrap();
+        let mut fields: Vec<&str> = serialized
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut columns = EventRecord::COLUMNS.to_vec();
+        fields.sort_unstable();
+        columns.sort_unstable();
+
+        assert_eq!(fields, columns);
+    }
+}
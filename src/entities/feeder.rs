@@ -0,0 +1,113 @@
+use dyn_partial_eq::*;
+
+use super::battery::BatteryPercent;
+use super::shared::{Information, Record};
+
+/// The bowl pets eat from, opening only for them.
+#[derive(Debug, DynPartialEq, PartialEq)]
+pub struct Feeder {
+    /// ID of the device.
+    pub id: u64,
+    pub name: String,
+    pub online: bool,
+    pub battery_voltage: f64,
+    /// Weight of food left in each bowl, in grams.
+    pub bowl_weights: Vec<f64>,
+}
+
+impl BatteryPercent for Feeder {
+    fn battery_voltage(&self) -> f64 {
+        self.battery_voltage
+    }
+}
+
+impl Information for Feeder {
+    /// Summary of a feeder.
+    ///
+    /// Example:
+    /// ```text
+    /// ✅ Feeder is online (battery: 51.09%, bowls: 23g / 41g)
+    /// ```
+    fn information(&self) -> String {
+        if self.online {
+            let bowls: Vec<String> = self
+                .bowl_weights
+                .iter()
+                .map(|weight| format!("{:.0}g", weight))
+                .collect();
+            format!(
+                "{} {} is online (battery: {:.2}%, bowls: {})",
+                self.battery_icon(),
+                self.name,
+                self.battery_percent(),
+                bowls.join(" / ")
+            )
+        } else {
+            format!("❌ {} is disconnected", self.name)
+        }
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "feeder",
+            id: Some(self.id),
+            name: self.name.clone(),
+            online: Some(self.online),
+            battery_voltage: Some(self.battery_voltage),
+            battery_percent: Some(self.rounded_battery_percent()),
+            first_bowl_weight: self.bowl_weights.first().copied(),
+            second_bowl_weight: self.bowl_weights.get(1).copied(),
+            ..Record::default()
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_formats_information_when_feeder_is_online() {
+        assert_eq!(
+            Feeder {
+                id: 790,
+                name: "Feeder".to_string(),
+                online: true,
+                battery_voltage: 5.6175,
+                bowl_weights: vec![23.4, 41.0],
+            }
+            .information(),
+            "✅ Feeder is online (battery: 51.09%, bowls: 23g / 41g)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_feeder_has_a_single_bowl() {
+        assert_eq!(
+            Feeder {
+                id: 790,
+                name: "Feeder".to_string(),
+                online: true,
+                battery_voltage: 4.9213,
+                bowl_weights: vec![12.0],
+            }
+            .information(),
+            "🪫 Feeder is online (battery: 7.58%, bowls: 12g)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_feeder_is_disconnected() {
+        assert_eq!(
+            Feeder {
+                id: 790,
+                name: "Feeder".to_string(),
+                online: false,
+                battery_voltage: 5.6175,
+                bowl_weights: vec![23.4, 41.0],
+            }
+            .information(),
+            "❌ Feeder is disconnected"
+        );
+    }
+}
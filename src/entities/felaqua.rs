@@ -0,0 +1,93 @@
+use dyn_partial_eq::*;
+
+use super::battery::BatteryPercent;
+use super::shared::{Information, Record};
+
+/// The water station pets drink from.
+#[derive(Debug, DynPartialEq, PartialEq)]
+pub struct Felaqua {
+    /// ID of the device.
+    pub id: u64,
+    pub name: String,
+    pub online: bool,
+    pub battery_voltage: f64,
+    /// Weight of water left in the tank, in grams.
+    pub water_weight: f64,
+}
+
+impl BatteryPercent for Felaqua {
+    fn battery_voltage(&self) -> f64 {
+        self.battery_voltage
+    }
+}
+
+impl Information for Felaqua {
+    /// Summary of a water station.
+    ///
+    /// Example:
+    /// ```text
+    /// ✅ Felaqua is online (battery: 51.09%, water: 450ml)
+    /// ```
+    fn information(&self) -> String {
+        if self.online {
+            format!(
+                "{} {} is online (battery: {:.2}%, water: {:.0}ml)",
+                self.battery_icon(),
+                self.name,
+                self.battery_percent(),
+                // A gram of water is a millilitre.
+                self.water_weight
+            )
+        } else {
+            format!("❌ {} is disconnected", self.name)
+        }
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "felaqua",
+            id: Some(self.id),
+            name: self.name.clone(),
+            online: Some(self.online),
+            battery_voltage: Some(self.battery_voltage),
+            battery_percent: Some(self.rounded_battery_percent()),
+            water_weight: Some(self.water_weight),
+            ..Record::default()
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_formats_information_when_felaqua_is_online() {
+        assert_eq!(
+            Felaqua {
+                id: 791,
+                name: "Felaqua".to_string(),
+                online: true,
+                battery_voltage: 5.6175,
+                water_weight: 450.2,
+            }
+            .information(),
+            "✅ Felaqua is online (battery: 51.09%, water: 450ml)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_felaqua_is_disconnected() {
+        assert_eq!(
+            Felaqua {
+                id: 791,
+                name: "Felaqua".to_string(),
+                online: false,
+                battery_voltage: 5.6175,
+                water_weight: 450.2,
+            }
+            .information(),
+            "❌ Felaqua is disconnected"
+        );
+    }
+}
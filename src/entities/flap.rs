@@ -1,36 +1,152 @@
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
 use dyn_partial_eq::*;
 
-use super::shared::Information;
+use super::battery::BatteryPercent;
+use super::curfew::Curfew;
+use super::shared::{Information, Record};
 
-/// When battery is full.
-const BATTERY_VOLTAGE_FULL: f64 = 1.6;
-/// When battery is low.
-const BATTERY_VOLTAGE_LOW: f64 = 1.2;
-/// How many batteries there are in the flap.
-const BATTERIES_COUNT: u8 = 4;
-
-/// The device pets use to go in and out.
-#[derive(Debug, DynPartialEq, PartialEq)]
+/// The device pets use to go in and out: a microchip pet door, or a cat flap.
+#[derive(Clone, Debug, DynPartialEq, PartialEq)]
 pub struct Flap {
+    /// ID of the device, used to control it.
+    pub id: u64,
+    /// ID of the household the flap belongs to.
+    pub household_id: u64,
     pub name: String,
     pub online: bool,
     pub battery_voltage: f64,
+    pub lock_mode: LockMode,
+    pub product: FlapProduct,
+    /// Windows during which the flap keeps pets in.
+    pub curfews: Vec<Curfew>,
+}
+
+/// The kinds of flaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlapProduct {
+    /// The microchip pet door.
+    PetDoor,
+    /// The microchip cat flap.
+    CatFlap,
+}
+
+impl FlapProduct {
+    /// How many curfews the flap can hold.
+    pub fn max_curfews(self) -> usize {
+        match self {
+            FlapProduct::PetDoor => 1,
+            FlapProduct::CatFlap => 4,
+        }
+    }
 }
 
-/// Remaining battery percentage level.
-trait BatteryPercent {
-    fn battery_percent(&self) -> f64;
+/// Which ways pets can go through a flap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    /// Pets can go in and out
+    None,
+    /// Pets are kept in: they can come in but not go out
+    In,
+    /// Pets are kept out: they can go out but not come in
+    Out,
+    /// Pets can neither come in nor go out
+    Both,
+    /// Pets are locked in or out according to the curfew
+    Curfew,
+    /// A mode we don't know about, with the number used by the API.
+    Unknown(u8),
+}
+
+/// Only the modes that can be set by hand are offered on the command line.
+impl ValueEnum for LockMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[LockMode::None, LockMode::In, LockMode::Out, LockMode::Both]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let help = match self {
+            LockMode::None => "Pets can go in and out",
+            LockMode::In => "Pets are kept in: they can come in but not go out",
+            LockMode::Out => "Pets are kept out: they can go out but not come in",
+            LockMode::Both => "Pets can neither come in nor go out",
+            LockMode::Curfew | LockMode::Unknown(_) => return None,
+        };
+        Some(PossibleValue::new(self.name()).help(help))
+    }
+}
+
+impl LockMode {
+    /// Lock mode from the number used by the API.
+    pub fn from_api(mode: u8) -> Self {
+        match mode {
+            0 => LockMode::None,
+            1 => LockMode::In,
+            2 => LockMode::Out,
+            3 => LockMode::Both,
+            4 => LockMode::Curfew,
+            other => LockMode::Unknown(other),
+        }
+    }
+
+    /// Number used by the API for this lock mode.
+    pub fn to_api(self) -> u8 {
+        match self {
+            LockMode::None => 0,
+            LockMode::In => 1,
+            LockMode::Out => 2,
+            LockMode::Both => 3,
+            LockMode::Curfew => 4,
+            LockMode::Unknown(mode) => mode,
+        }
+    }
+
+    /// Name used in machine-readable outputs.
+    pub fn name(self) -> &'static str {
+        match self {
+            LockMode::None => "none",
+            LockMode::In => "in",
+            LockMode::Out => "out",
+            LockMode::Both => "both",
+            LockMode::Curfew => "curfew",
+            LockMode::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Human friendly description.
+    pub fn description(self) -> &'static str {
+        match self {
+            LockMode::None => "unlocked",
+            LockMode::In => "locked in",
+            LockMode::Out => "locked out",
+            LockMode::Both => "locked",
+            LockMode::Curfew => "curfew",
+            LockMode::Unknown(_) => "in an unknown lock mode",
+        }
+    }
 }
 
 impl BatteryPercent for Flap {
-    fn battery_percent(&self) -> f64 {
-        let voltage_diff = BATTERY_VOLTAGE_FULL - BATTERY_VOLTAGE_LOW;
-        let voltage_per_battery = self.battery_voltage / BATTERIES_COUNT as f64;
-        let voltage_per_battery_diff = voltage_per_battery - BATTERY_VOLTAGE_LOW;
+    fn battery_voltage(&self) -> f64 {
+        self.battery_voltage
+    }
+}
 
-        return (voltage_per_battery_diff / voltage_diff * 100.0)
-            .min(100.0)
-            .max(0.0);
+impl Flap {
+    /// Curfews that are currently enabled, like `19:00-07:00, 12:00-14:00`.
+    fn enabled_curfews(&self) -> Option<String> {
+        let curfews: Vec<String> = self
+            .curfews
+            .iter()
+            .filter(|curfew| curfew.enabled)
+            .map(|curfew| curfew.to_string())
+            .collect();
+
+        if curfews.is_empty() {
+            None
+        } else {
+            Some(curfews.join(", "))
+        }
     }
 }
 
@@ -38,28 +154,42 @@ impl Information for Flap {
     /// Summary of a flap.
     ///
     /// Example:
-    /// ```
-    /// ✅ Flap is online (battery: 51.09%)
+    /// ```text
+    /// ✅ Flap is online (battery: 51.09%, unlocked, curfew: 19:00-07:00)
     /// ```
     fn information(&self) -> String {
         if self.online {
-            if self.battery_percent() > 10.0 {
-                format!(
-                    "✅ {} is online (battery: {:.2}%)",
-                    self.name,
-                    self.battery_percent()
-                )
-            } else {
-                format!(
-                    "🪫 {} is online (battery: {:.2}%)",
-                    self.name,
-                    self.battery_percent()
-                )
-            }
+            let curfews = match self.enabled_curfews() {
+                Some(curfews) => format!(", curfew: {}", curfews),
+                None => String::new(),
+            };
+            format!(
+                "{} {} is online (battery: {:.2}%, {}{})",
+                self.battery_icon(),
+                self.name,
+                self.battery_percent(),
+                self.lock_mode.description(),
+                curfews
+            )
         } else {
             format!("❌ {} is disconnected", self.name)
         }
     }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "flap",
+            id: Some(self.id),
+            name: self.name.clone(),
+            household_id: Some(self.household_id),
+            online: Some(self.online),
+            battery_voltage: Some(self.battery_voltage),
+            battery_percent: Some(self.rounded_battery_percent()),
+            lock_mode: Some(self.lock_mode.name()),
+            curfew: self.enabled_curfews(),
+            ..Record::default()
+        }
+    }
 }
 
 mod tests {
@@ -70,12 +200,17 @@ mod tests {
     fn it_formats_information_when_flap_is_online() {
         assert_eq!(
             Flap {
+                id: 456,
+                household_id: 42,
                 name: "Flap".to_string(),
                 online: true,
-                battery_voltage: 5.6175
+                battery_voltage: 5.6175,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                curfews: vec![],
             }
             .information(),
-            "✅ Flap is online (battery: 51.09%)"
+            "✅ Flap is online (battery: 51.09%, unlocked)"
         );
     }
 
@@ -83,12 +218,77 @@ mod tests {
     fn it_formats_information_when_flap_battery_is_low() {
         assert_eq!(
             Flap {
+                id: 456,
+                household_id: 42,
+                name: "Flap".to_string(),
+                online: true,
+                battery_voltage: 4.9213,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                curfews: vec![],
+            }
+            .information(),
+            "🪫 Flap is online (battery: 7.58%, unlocked)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_flap_is_locked() {
+        assert_eq!(
+            Flap {
+                id: 456,
+                household_id: 42,
+                name: "Flap".to_string(),
+                online: true,
+                battery_voltage: 5.6175,
+                lock_mode: LockMode::Out,
+                product: FlapProduct::PetDoor,
+                curfews: vec![],
+            }
+            .information(),
+            "✅ Flap is online (battery: 51.09%, locked out)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_flap_lock_mode_is_unknown() {
+        assert_eq!(
+            Flap {
+                id: 456,
+                household_id: 42,
+                name: "Flap".to_string(),
+                online: true,
+                battery_voltage: 5.6175,
+                lock_mode: LockMode::from_api(9),
+                product: FlapProduct::CatFlap,
+                curfews: vec![],
+            }
+            .information(),
+            "✅ Flap is online (battery: 51.09%, in an unknown lock mode)"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_flap_has_a_curfew() {
+        assert_eq!(
+            Flap {
+                id: 456,
+                household_id: 42,
                 name: "Flap".to_string(),
                 online: true,
-                battery_voltage: 4.9213
+                battery_voltage: 5.6175,
+                lock_mode: LockMode::None,
+                product: FlapProduct::CatFlap,
+                curfews: vec![
+                    "19:00-07:00".parse().unwrap(),
+                    Curfew {
+                        enabled: false,
+                        ..("12:00-14:00".parse().unwrap())
+                    },
+                ],
             }
             .information(),
-            "🪫 Flap is online (battery: 7.58%)"
+            "✅ Flap is online (battery: 51.09%, unlocked, curfew: 19:00-07:00)"
         );
     }
 
@@ -96,12 +296,45 @@ mod tests {
     fn it_formats_information_when_flap_is_disconnected() {
         assert_eq!(
             Flap {
+                id: 456,
+                household_id: 42,
                 name: "Flap".to_string(),
                 online: false,
-                battery_voltage: 5.6175
+                battery_voltage: 5.6175,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                curfews: vec![],
             }
             .information(),
             "❌ Flap is disconnected"
         );
     }
+
+    #[test]
+    fn it_builds_a_record() {
+        assert_eq!(
+            Flap {
+                id: 456,
+                household_id: 42,
+                name: "Flap".to_string(),
+                online: true,
+                battery_voltage: 5.6,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                curfews: vec![],
+            }
+            .record(),
+            Record {
+                kind: "flap",
+                id: Some(456),
+                name: "Flap".to_string(),
+                household_id: Some(42),
+                online: Some(true),
+                battery_voltage: Some(5.6),
+                battery_percent: Some(50.0),
+                lock_mode: Some("none"),
+                ..Record::default()
+            }
+        );
+    }
 }
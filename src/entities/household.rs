@@ -0,0 +1,48 @@
+use dyn_partial_eq::*;
+
+use super::shared::{Information, Record};
+
+/// A home, an office, … : the place pets and devices belong to.
+#[derive(Debug, DynPartialEq, PartialEq)]
+pub struct Household {
+    pub id: u64,
+    pub name: String,
+}
+
+impl Information for Household {
+    /// Summary of a household.
+    ///
+    /// Example:
+    /// ```text
+    /// 🏘️ Home (id: 42)
+    /// ```
+    fn information(&self) -> String {
+        format!("🏘️ {} (id: {})", self.name, self.id)
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "household",
+            name: self.name.clone(),
+            household_id: Some(self.id),
+            ..Record::default()
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_formats_information() {
+        assert_eq!(
+            Household {
+                id: 42,
+                name: "Home".to_string(),
+            }
+            .information(),
+            "🏘️ Home (id: 42)"
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use dyn_partial_eq::*;
 
-use super::shared::Information;
+use super::shared::{Information, Record};
 
 /// The surepet hub, responsible of collecting information from other devices.
 #[derive(Debug, DynPartialEq, PartialEq)]
 pub struct Hub {
+    /// ID of the device.
+    pub id: u64,
+    /// ID of the household the hub belongs to.
+    pub household_id: u64,
     pub name: String,
     pub online: bool,
 }
@@ -13,7 +17,7 @@ impl Information for Hub {
     /// Summary of the hub.
     ///
     /// Example:
-    /// ```
+    /// ```text
     /// ✅ Hub is online
     /// ```
     fn information(&self) -> String {
@@ -23,6 +27,17 @@ impl Information for Hub {
             format!("❌ {} is disconnected", self.name)
         }
     }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "hub",
+            name: self.name.clone(),
+            id: Some(self.id),
+            household_id: Some(self.household_id),
+            online: Some(self.online),
+            ..Record::default()
+        }
+    }
 }
 
 mod tests {
@@ -33,6 +48,8 @@ mod tests {
     fn it_formats_information_when_hub_is_online() {
         assert_eq!(
             Hub {
+                id: 123,
+                household_id: 42,
                 name: "Hub".to_string(),
                 online: true,
             }
@@ -45,6 +62,8 @@ mod tests {
     fn it_formats_information_when_hub_is_disconnected() {
         assert_eq!(
             Hub {
+                id: 123,
+                household_id: 42,
                 name: "Hub".to_string(),
                 online: false,
             }
@@ -1,4 +1,15 @@
+pub mod account;
+pub mod battery;
+pub mod curfew;
+pub mod event;
+pub mod feeder;
+pub mod felaqua;
 pub mod flap;
+pub mod household;
 pub mod hub;
 pub mod pet;
 pub mod shared;
+pub mod status;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod unknown_device;
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 #[cfg(not(test))]
 use chrono::Local;
 #[cfg(not(test))]
@@ -5,16 +7,69 @@ use chrono::SecondsFormat;
 use chrono::{DateTime, FixedOffset};
 use dyn_partial_eq::*;
 use humantime::format_duration;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::shared::{Information, Record};
+
+/// Where a pet is, as last seen by a flap.
+///
+/// Deserialized from the number used by the API, serialized as its name.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(from = "u8")]
+pub enum PetPosition {
+    Inside,
+    Outside,
+    /// A position the API uses but we don't know about, e.g. for pets never seen by a flap.
+    Unknown(u8),
+}
+
+impl PetPosition {
+    /// Number used by the API for this position.
+    pub fn to_api(self) -> u8 {
+        match self {
+            PetPosition::Inside => 1,
+            PetPosition::Outside => 2,
+            PetPosition::Unknown(position) => position,
+        }
+    }
 
-use super::shared::Information;
+    /// Name used in machine-readable outputs.
+    pub fn name(self) -> &'static str {
+        match self {
+            PetPosition::Inside => "inside",
+            PetPosition::Outside => "outside",
+            PetPosition::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<u8> for PetPosition {
+    fn from(position: u8) -> Self {
+        match position {
+            1 => PetPosition::Inside,
+            2 => PetPosition::Outside,
+            other => PetPosition::Unknown(other),
+        }
+    }
+}
+
+impl Serialize for PetPosition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
 
 /// Represent a pet.
 #[derive(Debug, Eq, DynPartialEq, PartialEq)]
 pub struct Pet {
+    /// ID of the pet, used to update it.
+    pub id: u64,
+    /// ID of the household the pet belongs to.
+    pub household_id: u64,
     /// Name of the pet.
     pub name: String,
-    /// Whether the pet is inside (1) or outside (2).
-    pub position: u8,
+    /// Whether the pet is inside or outside.
+    pub position: PetPosition,
     /// Since when the pet is inside / outside.
     pub position_since: DateTime<FixedOffset>,
 }
@@ -23,22 +78,37 @@ impl Information for Pet {
     /// Summary of pet.
     ///
     /// Example:
-    /// ```
+    /// ```text
     /// 🏠 Garfield is inside since 1h 27m 8s
     /// ```
     fn information(&self) -> String {
         match self.position {
-            1 => format!(
+            PetPosition::Inside => format!(
                 "🏠 {} is inside since {}",
                 self.name,
                 position_duration(self.position_since)
             ),
-            2 => format!(
+            PetPosition::Outside => format!(
                 "🏡 {} is outside since {}",
                 self.name,
                 position_duration(self.position_since)
             ),
-            _ => panic!("Invalid pet position"),
+            PetPosition::Unknown(position) => {
+                format!("❓ {} is at an unknown position ({})", self.name, position)
+            }
+        }
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "pet",
+            name: self.name.clone(),
+            id: Some(self.id),
+            household_id: Some(self.household_id),
+            position: Some(self.position),
+            position_since: Some(self.position_since.to_rfc3339()),
+            duration: Some(elapsed_since(self.position_since).as_secs()),
+            ..Record::default()
         }
     }
 }
@@ -46,24 +116,26 @@ impl Information for Pet {
 /// Human readable duration since the pet position has not changed.
 ///
 /// Example:
-/// ```
+/// ```text
 /// 2h 12m 53s
 /// ```
 fn position_duration(since: DateTime<FixedOffset>) -> String {
+    format_duration(elapsed_since(since)).to_string()
+}
+
+/// Time elapsed since the given date, or zero if it is in the future.
+fn elapsed_since(since: DateTime<FixedOffset>) -> Duration {
     #[cfg(not(test))]
     // Convert to rfc3339 to remove the milliseconds.
     let now = Local::now().to_rfc3339_opts(SecondsFormat::Secs, true);
     #[cfg(test)]
     let now = "2023-01-01T12:00:00+00:00";
 
-    return format_duration(
-        now.parse::<DateTime<FixedOffset>>()
-            .unwrap()
-            .signed_duration_since(since)
-            .to_std()
-            .unwrap(),
-    )
-    .to_string();
+    now.parse::<DateTime<FixedOffset>>()
+        .unwrap()
+        .signed_duration_since(since)
+        .to_std()
+        .unwrap_or_default()
 }
 
 mod tests {
@@ -74,8 +146,10 @@ mod tests {
     fn it_formats_information_when_pet_is_inside() {
         assert_eq!(
             Pet {
+                id: 789,
+                household_id: 42,
                 name: "Garfield".to_string(),
-                position: 1,
+                position: PetPosition::Inside,
                 position_since: "2023-01-01T10:32:52+00:00"
                     .parse::<DateTime<FixedOffset>>()
                     .unwrap()
@@ -89,8 +163,10 @@ mod tests {
     fn it_formats_information_when_pet_is_outside() {
         assert_eq!(
             Pet {
+                id: 789,
+                household_id: 42,
                 name: "Garfield".to_string(),
-                position: 2,
+                position: PetPosition::Outside,
                 position_since: "2022-12-28T16:09:52+00:00"
                     .parse::<DateTime<FixedOffset>>()
                     .unwrap()
@@ -99,4 +175,64 @@ mod tests {
             "🏡 Garfield is outside since 3days 19h 50m 8s"
         );
     }
+
+    #[test]
+    fn it_builds_a_record() {
+        assert_eq!(
+            Pet {
+                id: 789,
+                household_id: 42,
+                name: "Garfield".to_string(),
+                position: PetPosition::Outside,
+                position_since: "2022-12-31T10:00:00+00:00"
+                    .parse::<DateTime<FixedOffset>>()
+                    .unwrap()
+            }
+            .record(),
+            Record {
+                kind: "pet",
+                name: "Garfield".to_string(),
+                id: Some(789),
+                household_id: Some(42),
+                position: Some(PetPosition::Outside),
+                position_since: Some("2022-12-31T10:00:00+00:00".to_string()),
+                duration: Some(93600),
+                ..Record::default()
+            }
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_pet_position_is_unknown() {
+        assert_eq!(
+            Pet {
+                id: 789,
+                household_id: 42,
+                name: "Garfield".to_string(),
+                position: PetPosition::Unknown(0),
+                position_since: "2023-01-01T10:32:52+00:00"
+                    .parse::<DateTime<FixedOffset>>()
+                    .unwrap()
+            }
+            .information(),
+            "❓ Garfield is at an unknown position (0)"
+        );
+    }
+
+    #[test]
+    fn it_converts_positions() {
+        assert_eq!(
+            serde_json::from_str::<Vec<PetPosition>>("[1, 2, 3]").unwrap(),
+            vec![
+                PetPosition::Inside,
+                PetPosition::Outside,
+                PetPosition::Unknown(3)
+            ]
+        );
+        assert_eq!(PetPosition::Unknown(3).to_api(), 3);
+        assert_eq!(
+            serde_json::to_string(&PetPosition::Outside).unwrap(),
+            r#""outside""#
+        );
+    }
 }
@@ -1,6 +1,130 @@
 use dyn_partial_eq::*;
+use serde::Serialize;
+
+use super::pet::PetPosition;
 
 #[dyn_partial_eq]
 pub trait Information: std::fmt::Debug {
     fn information(&self) -> String;
+
+    /// Flat representation, used for machine-readable outputs.
+    fn record(&self) -> Record;
+}
+
+/// A flat, serializable representation printed as CSV or tables, with a fixed set of columns.
+pub trait Columns: Serialize {
+    /// Names of the serialized fields, in the order of the columns.
+    const COLUMNS: &'static [&'static str];
+}
+
+/// Everything we know about a pet or a device, with stable field names.
+///
+/// Fields that don't apply to an entity (e.g. the battery of a pet) are left out of JSON and YAML,
+/// and empty in CSV, whose columns are always the ones of [`Record::COLUMNS`].
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Record {
+    /// What the entity is: `hub`, `flap`, `feeder`, `felaqua`, `pet`, `household`, or `unknown`.
+    pub kind: &'static str,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub online: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_voltage: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_percent: Option<f64>,
+    /// `inside`, `outside` or `unknown`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<PetPosition>,
+    /// RFC 3339 date of the last position change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_since: Option<String>,
+    /// Seconds elapsed since the last position change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    /// Grams of food left in the first bowl of a feeder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_bowl_weight: Option<f64>,
+    /// Grams of food left in the second bowl of a feeder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second_bowl_weight: Option<f64>,
+    /// Grams of water left in a water station.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub water_weight: Option<f64>,
+    /// Internal ID of an unsupported device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<u8>,
+    /// Which ways pets can go through a flap: `none`, `in`, `out`, `both`, `curfew` or
+    /// `unknown`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock_mode: Option<&'static str>,
+    /// Enabled curfews of a flap, like `19:00-07:00, 12:00-14:00`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub curfew: Option<String>,
+    /// ID of the household of a pet, a hub or a flap; or of a household itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub household_id: Option<u64>,
+    /// ID of a pet, a hub or a flap, as used by the API.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+}
+
+impl Columns for Record {
+    /// New fields are only ever added at the end, so that scripts reading CSV keep working.
+    const COLUMNS: &'static [&'static str] = &[
+        "kind",
+        "name",
+        "online",
+        "battery_voltage",
+        "battery_percent",
+        "position",
+        "position_since",
+        "duration",
+        "first_bowl_weight",
+        "second_bowl_weight",
+        "water_weight",
+        "product_id",
+        "lock_mode",
+        "curfew",
+        "household_id",
+        "id",
+    ];
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_has_a_column_per_field() {
+        let record = Record {
+            kind: "pet",
+            name: "Garfield".to_string(),
+            online: Some(true),
+            battery_voltage: Some(5.6),
+            battery_percent: Some(50.0),
+            position: Some(PetPosition::Inside),
+            position_since: Some("2023-01-01T12:00:00+00:00".to_string()),
+            duration: Some(60),
+            first_bowl_weight: Some(1.0),
+            second_bowl_weight: Some(2.0),
+            water_weight: Some(3.0),
+            product_id: Some(3),
+            lock_mode: Some("both"),
+            curfew: Some("19:00-07:00".to_string()),
+            household_id: Some(2),
+            id: Some(1),
+        };
+        let serialized = serde_json::to_value(record).unwrap();
+        let mut fields: Vec<&str> = serialized
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut columns = Record::COLUMNS.to_vec();
+        fields.sort_unstable();
+        columns.sort_unstable();
+
+        assert_eq!(fields, columns);
+    }
 }
@@ -0,0 +1,23 @@
+use super::flap::Flap;
+use super::pet::Pet;
+use super::shared::Information;
+
+/// Devices and pets, fetched together.
+#[derive(Debug)]
+pub struct Status {
+    pub devices: Vec<Box<dyn Information>>,
+    /// The flaps among the devices, for what only flaps have, like curfews.
+    pub flaps: Vec<Flap>,
+    pub pets: Vec<Pet>,
+}
+
+impl Status {
+    /// Everything to print, devices first and pets after.
+    pub fn overview(self) -> Vec<Box<dyn Information>> {
+        let pets = self
+            .pets
+            .into_iter()
+            .map(|pet| Box::new(pet) as Box<dyn Information>);
+        self.devices.into_iter().chain(pets).collect()
+    }
+}
@@ -0,0 +1,55 @@
+//! Pets and devices shared by the tests, as the API would describe them.
+
+use super::flap::{Flap, FlapProduct, LockMode};
+use super::hub::Hub;
+use super::pet::{Pet, PetPosition};
+use super::status::Status;
+
+/// The hub of the household.
+pub(crate) fn hub() -> Hub {
+    Hub {
+        id: 123,
+        household_id: 42,
+        name: "Hub".to_string(),
+        online: true,
+    }
+}
+
+/// A cat flap of the household, keeping pets in.
+pub(crate) fn flap() -> Flap {
+    Flap {
+        id: 456,
+        household_id: 42,
+        name: "Buanderie".to_string(),
+        online: true,
+        battery_voltage: 5.6,
+        lock_mode: LockMode::In,
+        product: FlapProduct::CatFlap,
+        curfews: vec![],
+    }
+}
+
+/// A pet of the household, inside since the morning.
+pub(crate) fn pet() -> Pet {
+    Pet {
+        id: 789,
+        household_id: 42,
+        name: "Garfield".to_string(),
+        position: PetPosition::Inside,
+        position_since: "2023-01-01T10:00:00+00:00".parse().unwrap(),
+    }
+}
+
+/// The hub, the flap and the pet, fetched together.
+pub(crate) fn status() -> Status {
+    status_of(hub(), flap(), vec![pet()])
+}
+
+/// A hub, a flap and pets, fetched together.
+pub(crate) fn status_of(hub: Hub, flap: Flap, pets: Vec<Pet>) -> Status {
+    Status {
+        devices: vec![Box::new(hub), Box::new(flap.clone())],
+        flaps: vec![flap],
+        pets,
+    }
+}
@@ -0,0 +1,85 @@
+use dyn_partial_eq::*;
+use serde_json::Value;
+
+use super::shared::{Information, Record};
+
+/// A device we don't support (yet), as reported by the API.
+#[derive(Debug, DynPartialEq, PartialEq)]
+pub struct UnknownDevice {
+    /// ID of the device.
+    pub id: u64,
+    pub name: String,
+    /// Internal ID the API uses for this kind of device.
+    pub product_id: u8,
+    pub online: bool,
+    /// Status as returned by the API, left untouched.
+    pub status: Value,
+}
+
+impl Information for UnknownDevice {
+    /// Summary of an unsupported device.
+    ///
+    /// Example:
+    /// ```text
+    /// ❓ Gadget (unsupported product 42) is online
+    /// ```
+    fn information(&self) -> String {
+        if self.online {
+            format!(
+                "❓ {} (unsupported product {}) is online",
+                self.name, self.product_id
+            )
+        } else {
+            format!(
+                "❓ {} (unsupported product {}) is disconnected",
+                self.name, self.product_id
+            )
+        }
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            kind: "unknown",
+            id: Some(self.id),
+            name: self.name.clone(),
+            product_id: Some(self.product_id),
+            online: Some(self.online),
+            ..Record::default()
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_formats_information_when_device_is_online() {
+        assert_eq!(
+            UnknownDevice {
+                id: 123,
+                name: "Gadget".to_string(),
+                product_id: 42,
+                online: true,
+                status: Value::Null,
+            }
+            .information(),
+            "❓ Gadget (unsupported product 42) is online"
+        );
+    }
+
+    #[test]
+    fn it_formats_information_when_device_is_disconnected() {
+        assert_eq!(
+            UnknownDevice {
+                id: 123,
+                name: "Gadget".to_string(),
+                product_id: 42,
+                online: false,
+                status: Value::Null,
+            }
+            .information(),
+            "❓ Gadget (unsupported product 42) is disconnected"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+
+/// Everything that can go wrong while talking to the surepet API.
+#[derive(Debug)]
+pub enum SurepetError {
+    /// The request could not be sent, or the response could not be read.
+    Network(reqwest::Error),
+    /// The API answered with a status we don't handle.
+    Http { status: StatusCode, body: String },
+    /// The API rejected our credentials or token.
+    Unauthorized,
+    /// The response didn't match the shape we expected.
+    Decode { path: String, message: String },
+    /// A required environment variable is not set.
+    MissingCredentials(String),
+    /// The API returned a device we don't know about.
+    UnknownProduct(u8),
+    /// A file (token, configuration, …) could not be read from or written to disk.
+    Io(std::io::Error),
+    /// The configuration file is not valid.
+    Config { path: String, message: String },
+    /// The output could not be produced in the requested format.
+    Encode(String),
+    /// There is no such pet or device.
+    NotFound(String),
+    /// The hub never relayed a change to the given device.
+    Unacknowledged(String),
+    /// The curfews cannot be set on the flap.
+    InvalidCurfew(String),
+    /// The command supposed to print the password failed.
+    PasswordCommand { command: String, message: String },
+    /// The MQTT broker could not be reached, or is not a valid one.
+    Mqtt(String),
+}
+
+pub type Result<T> = std::result::Result<T, SurepetError>;
+
+impl fmt::Display for SurepetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurepetError::Network(error) => write!(f, "Unable to reach the API: {}", error),
+            SurepetError::Http { status, body } => {
+                write!(f, "Uh oh! The API answered with {}: {}", status, body)
+            }
+            SurepetError::Unauthorized => write!(f, "Invalid credentials"),
+            SurepetError::Decode { path, message } => write!(
+                f,
+                "Hm, the response didn't match the shape we expected at `{}`: {}",
+                path, message
+            ),
+            SurepetError::MissingCredentials(variable) => {
+                write!(f, "Please set `{}` env variable", variable)
+            }
+            SurepetError::UnknownProduct(product_id) => {
+                write!(f, "This device is unknown! (product {})", product_id)
+            }
+            SurepetError::Io(error) => write!(f, "Unable to access the filesystem: {}", error),
+            SurepetError::Config { path, message } => {
+                write!(f, "Invalid configuration in {}: {}", path, message)
+            }
+            SurepetError::Encode(message) => write!(f, "Unable to format the output: {}", message),
+            SurepetError::NotFound(what) => write!(f, "Cannot find {}", what),
+            SurepetError::Unacknowledged(name) => write!(
+                f,
+                "{} did not acknowledge the change, is the hub online?",
+                name
+            ),
+            SurepetError::InvalidCurfew(reason) => write!(f, "Invalid curfew: {}", reason),
+            SurepetError::PasswordCommand { command, message } => write!(
+                f,
+                "Unable to get the password from `{}`: {}",
+                command, message
+            ),
+            SurepetError::Mqtt(message) => {
+                write!(f, "Unable to talk to the MQTT broker: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SurepetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SurepetError::Network(error) => Some(error),
+            SurepetError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SurepetError {
+    fn from(error: reqwest::Error) -> Self {
+        SurepetError::Network(error)
+    }
+}
+
+impl From<std::io::Error> for SurepetError {
+    fn from(error: std::io::Error) -> Self {
+        SurepetError::Io(error)
+    }
+}
+
+impl From<rumqttc::ClientError> for SurepetError {
+    fn from(error: rumqttc::ClientError) -> Self {
+        SurepetError::Mqtt(error.to_string())
+    }
+}
+
+impl From<rumqttc::ConnectionError> for SurepetError {
+    fn from(error: rumqttc::ConnectionError) -> Self {
+        SurepetError::Mqtt(error.to_string())
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for SurepetError {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        SurepetError::Decode {
+            path: error.path().to_string(),
+            message: error.inner().to_string(),
+        }
+    }
+}
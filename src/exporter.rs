@@ -0,0 +1,370 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::client::SurepetClient;
+use crate::entities::pet::{Pet, PetPosition};
+use crate::entities::shared::Record;
+use crate::entities::status::Status;
+use crate::error::Result;
+use crate::watch::Poller;
+
+const METRICS_PATH: &str = "/metrics";
+/// Longest request we read, headers included: scrapers send a few hundred bytes.
+const MAX_REQUEST_SIZE: usize = 8192;
+#[cfg(not(test))]
+/// How long a client has to send its request before we hang up.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(test)]
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// What scrapes are answered with, until the next refresh.
+#[derive(Debug, Default)]
+struct Scrape {
+    /// Gauges of the last successful refresh.
+    gauges: String,
+    /// Whether the last refresh succeeded.
+    up: bool,
+}
+
+/// Serve the metrics of pets and devices on `listen`, refreshing them from the API every
+/// `interval`.
+///
+/// Scrapes never reach the API: they get the values of the last successful refresh, and
+/// `surepet_up` tells whether the following ones failed.
+pub async fn serve(client: &SurepetClient, listen: SocketAddr, interval: Duration) -> Result<()> {
+    let listener = TcpListener::bind(listen).await?;
+    let scrape = Arc::new(Mutex::new(Scrape::default()));
+
+    tokio::try_join!(
+        refresh(client, &scrape, interval),
+        accept(listener, &scrape)
+    )?;
+    Ok(())
+}
+
+async fn refresh(client: &SurepetClient, scrape: &Mutex<Scrape>, interval: Duration) -> Result<()> {
+    let mut poller = Poller::new(client, interval);
+
+    loop {
+        match poller.next().await? {
+            Some(status) => {
+                *scrape.lock().unwrap() = Scrape {
+                    gauges: gauges(&status),
+                    up: true,
+                }
+            }
+            None => scrape.lock().unwrap().up = false,
+        }
+    }
+}
+
+async fn accept(listener: TcpListener, scrape: &Arc<Mutex<Scrape>>) -> Result<()> {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let scrape = Arc::clone(scrape);
+                tokio::spawn(async move {
+                    if let Err(error) = answer(stream, &scrape).await {
+                        eprintln!("{}", error);
+                    }
+                });
+            }
+            // Like running out of file descriptors: other connections may still work.
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+async fn answer(mut stream: TcpStream, scrape: &Mutex<Scrape>) -> Result<()> {
+    let Ok(request) = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await else {
+        // The connection is dropped, so that silent clients don't keep it open.
+        return Ok(());
+    };
+    let request = request?;
+
+    let response = respond(&String::from_utf8_lossy(&request), &scrape.lock().unwrap());
+    stream.write_all(response.as_bytes()).await?;
+    Ok(stream.shutdown().await?)
+}
+
+/// Read the request line and headers, up to `MAX_REQUEST_SIZE`.
+async fn read_request(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n")
+        && request.len() < MAX_REQUEST_SIZE
+    {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    Ok(request)
+}
+
+/// The HTTP response to a request: the metrics for `GET /metrics`, an error otherwise.
+fn respond(request: &str, scrape: &Scrape) -> String {
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some(METRICS_PATH)) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            metrics(scrape),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        ),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn metrics(scrape: &Scrape) -> String {
+    let mut metrics = String::new();
+    gauge(
+        &mut metrics,
+        "surepet_up",
+        "Whether the last refresh from the API succeeded",
+        [(String::new(), if scrape.up { 1.0 } else { 0.0 })],
+    );
+    metrics.push_str(&scrape.gauges);
+    metrics
+}
+
+/// Gauges of pets and devices, in the Prometheus text format.
+///
+/// Names can be shared by pets or devices of different households, so they are labelled with
+/// their IDs too.
+fn gauges(status: &Status) -> String {
+    let devices: Vec<_> = status
+        .devices
+        .iter()
+        .map(|device| device.record())
+        .collect();
+    let flaps = devices.iter().filter(|device| device.kind == "flap");
+    let mut gauges = String::new();
+
+    gauge(
+        &mut gauges,
+        "surepet_device_online",
+        "Whether the device is connected",
+        devices.iter().filter_map(|device| {
+            let labels = labels(&[
+                ("id", &device.id?.to_string()),
+                ("name", &device.name),
+                ("product", device.kind),
+            ]);
+            Some((labels, bool_value(device.online?)))
+        }),
+    );
+    gauge(
+        &mut gauges,
+        "surepet_flap_battery_voltage",
+        "Voltage of all the batteries of the flap",
+        flaps
+            .clone()
+            .filter_map(|flap| Some((flap_labels(flap)?, flap.battery_voltage?))),
+    );
+    gauge(
+        &mut gauges,
+        "surepet_flap_battery_percent",
+        "Battery level of the flap",
+        flaps.filter_map(|flap| Some((flap_labels(flap)?, flap.battery_percent?))),
+    );
+    gauge(
+        &mut gauges,
+        "surepet_pet_inside",
+        "Whether the pet is inside, absent when its position is unknown",
+        status.pets.iter().filter_map(|pet| {
+            let inside = match pet.position {
+                PetPosition::Inside => true,
+                PetPosition::Outside => false,
+                PetPosition::Unknown(_) => return None,
+            };
+            Some((pet_labels(pet), bool_value(inside)))
+        }),
+    );
+    gauge(
+        &mut gauges,
+        "surepet_pet_position_since_seconds",
+        "Unix time of the last position change of the pet",
+        status
+            .pets
+            .iter()
+            .map(|pet| (pet_labels(pet), pet.position_since.timestamp() as f64)),
+    );
+    gauges
+}
+
+fn flap_labels(flap: &Record) -> Option<String> {
+    Some(labels(&[
+        ("id", &flap.id?.to_string()),
+        ("name", &flap.name),
+    ]))
+}
+
+fn pet_labels(pet: &Pet) -> String {
+    labels(&[("id", &pet.id.to_string()), ("name", &pet.name)])
+}
+
+/// Append a gauge and its samples, each made of its labels and its value.
+fn gauge(
+    metrics: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(metrics, "# HELP {} {}", name, help);
+    let _ = writeln!(metrics, "# TYPE {} gauge", name);
+    for (labels, value) in samples {
+        let _ = writeln!(metrics, "{}{} {}", name, labels, value);
+    }
+}
+
+fn labels(labels: &[(&str, &str)]) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', r"\\")
+                .replace('"', r#"\""#)
+                .replace('\n', r"\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+fn bool_value(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::entities::flap::{Flap, FlapProduct, LockMode};
+    #[cfg(test)]
+    use crate::entities::test_support;
+
+    #[cfg(test)]
+    fn status() -> Status {
+        test_support::status_of(
+            test_support::hub(),
+            Flap {
+                online: false,
+                lock_mode: LockMode::None,
+                product: FlapProduct::PetDoor,
+                ..test_support::flap()
+            },
+            vec![
+                Pet {
+                    name: "Garfield \"the cat\"".to_string(),
+                    ..test_support::pet()
+                },
+                Pet {
+                    id: 790,
+                    name: "Arlene".to_string(),
+                    position: PetPosition::Unknown(3),
+                    position_since: "2023-01-01T11:00:00+00:00".parse().unwrap(),
+                    ..test_support::pet()
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn it_exports_gauges() {
+        let gauges = gauges(&status());
+
+        assert!(gauges.contains("# TYPE surepet_device_online gauge\n"));
+        assert!(
+            gauges.contains("surepet_device_online{id=\"123\",name=\"Hub\",product=\"hub\"} 1\n")
+        );
+        assert!(gauges
+            .contains("surepet_device_online{id=\"456\",name=\"Buanderie\",product=\"flap\"} 0\n"));
+        assert!(
+            gauges.contains("surepet_flap_battery_voltage{id=\"456\",name=\"Buanderie\"} 5.6\n")
+        );
+        assert!(gauges.contains("surepet_flap_battery_percent{id=\"456\",name=\"Buanderie\"} 50\n"));
+        assert!(
+            gauges.contains("surepet_pet_inside{id=\"789\",name=\"Garfield \\\"the cat\\\"\"} 1\n")
+        );
+        assert!(!gauges.contains("surepet_pet_inside{id=\"790\""));
+        assert!(gauges.contains(
+            "surepet_pet_position_since_seconds{id=\"790\",name=\"Arlene\"} 1672570800\n"
+        ));
+    }
+
+    #[test]
+    fn it_answers_scrapes_from_the_cache() {
+        let scrape = Scrape {
+            gauges: "# some gauges\n".to_string(),
+            up: false,
+        };
+
+        let response = respond("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n", &scrape);
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n# HELP surepet_up Whether the last refresh from the API succeeded\n# TYPE surepet_up gauge\nsurepet_up 0\n# some gauges\n"));
+    }
+
+    #[test]
+    fn it_tells_apart_pets_of_the_same_name() {
+        let mut status = status();
+        status.pets[1].name = status.pets[0].name.clone();
+
+        let gauges = gauges(&status);
+
+        assert!(gauges.contains("surepet_pet_position_since_seconds{id=\"789\","));
+        assert!(gauges.contains("surepet_pet_position_since_seconds{id=\"790\","));
+    }
+
+    #[test]
+    fn it_hangs_up_on_silent_clients() {
+        tokio_test::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+
+            answer(stream, &Mutex::new(Scrape::default()))
+                .await
+                .unwrap();
+
+            let mut response = vec![];
+            client.read_to_end(&mut response).await.unwrap();
+            assert!(response.is_empty());
+        });
+    }
+
+    #[test]
+    fn it_only_answers_metrics() {
+        let scrape = Scrape::default();
+
+        assert!(respond("GET / HTTP/1.1\r\n\r\n", &scrape).starts_with("HTTP/1.1 404 Not Found"));
+        assert!(respond("POST /metrics HTTP/1.1\r\n\r\n", &scrape)
+            .starts_with("HTTP/1.1 405 Method Not Allowed"));
+    }
+}
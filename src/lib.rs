@@ -0,0 +1,19 @@
+//! Unofficial client for the [surepet API](https://www.surepetcare.io/).
+//!
+//! Start with a [`SurepetClient`], then query your pets and devices with it.
+
+pub mod api;
+pub mod check;
+pub mod client;
+pub mod config;
+pub mod entities;
+pub mod error;
+pub mod exporter;
+pub mod mqtt;
+pub mod notify;
+pub mod output;
+pub mod utils;
+pub mod watch;
+
+pub use client::{Credentials, Password, RetryPolicy, SurepetClient, BASE_URL};
+pub use error::{Result, SurepetError};
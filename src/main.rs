@@ -1,43 +1,605 @@
-use clap::{Parser, Subcommand};
+use std::env;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
 
-use crate::api::devices::devices;
-use crate::api::pets::pets;
-use crate::entities::shared::Information;
+use chrono::{DateTime, FixedOffset};
+use clap::{Parser, Subcommand, ValueEnum};
 
-mod api;
-mod entities;
-mod utils;
+use surepet_rs::check::{check, offline_flaps_path, Severity};
+use surepet_rs::config::{CheckRules, Config, Webhook};
+use surepet_rs::entities::curfew::Curfew;
+use surepet_rs::entities::flap::LockMode;
+use surepet_rs::entities::household::Household;
+use surepet_rs::entities::pet::{Pet, PetPosition};
+use surepet_rs::entities::shared::Information;
+use surepet_rs::exporter;
+use surepet_rs::mqtt::{self, MqttSettings};
+use surepet_rs::notify::notify;
+use surepet_rs::output::{render, OutputFormat};
+use surepet_rs::utils::authentication::{
+    default_token_path, forget_token, migrate_legacy_tokens, profile_token_path, read_token,
+    store_token, StoredToken,
+};
+use surepet_rs::utils::paths::{config_path, state_dir};
+use surepet_rs::utils::time::{now, parse_time};
+use surepet_rs::watch::{Poller, Snapshot};
+use surepet_rs::{Result, RetryPolicy, SurepetClient, SurepetError, BASE_URL};
 
 #[derive(Debug, Parser)]
 #[command(name = "surepet")]
 #[command(about = "Surepet unofficial CLI", long_about = None)]
 struct Cli {
+    /// Profile of the configuration file to use, defaults to its `default_profile`
+    #[arg(long, global = true, env = "SUREPET_PROFILE")]
+    profile: Option<String>,
+
+    /// Base url of the API, defaults to the `base_url` of the profile or of the configuration
+    /// file, or the official API
+    #[arg(long, global = true, env = "SUREPET_BASE_URL")]
+    base_url: Option<String>,
+
+    /// How to print pets and devices, defaults to the `output` of the profile, or text
+    #[arg(short, long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Fail on devices that are not supported, instead of listing them as unknown
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Only deal with the pets and devices of this household, given by name or ID, defaults to
+    /// the `household` of the profile
+    #[arg(long, global = true)]
+    household: Option<String>,
+
+    /// How long to wait for a connection to the API
+    #[arg(long, global = true, default_value = "10s", value_parser = humantime::parse_duration)]
+    connect_timeout: Duration,
+
+    /// How long to wait for a response of the API
+    #[arg(long, global = true, default_value = "30s", value_parser = humantime::parse_duration)]
+    timeout: Duration,
+
+    /// How many times to retry a read that failed because of the network or the API
+    #[arg(long, global = true, default_value_t = RetryPolicy::default().retries)]
+    retries: u32,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
+    /// Log in, and keep the token for the next commands
+    Login {
+        /// Email of the account, defaults to the one of the profile or `SUREPET_EMAIL`, or is asked
+        #[arg(long)]
+        email: Option<String>,
+        /// Read the password from the standard input instead of asking for it
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Forget the token
+    Logout {
+        /// Also ask the API to invalidate the token
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Show the account in use
+    Whoami {},
+    /// List the households of the account
+    Households {},
     /// List devices
     Devices {},
     /// List pets
     Pets {},
+    /// List devices, then pets
+    Status {},
+    /// List what happened in the household, oldest first
+    Timeline {
+        /// Only list the events of this pet
+        #[arg(long)]
+        pet: Option<String>,
+        /// How far back to go, like 24h, 14:12 or 2023-02-05 14:12
+        #[arg(long, default_value = "24h", value_parser = parse_since)]
+        since: DateTime<FixedOffset>,
+        /// Only list the most recent events
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Print pet movements and device changes as they happen, until interrupted
+    Watch {
+        /// How long to wait between two polls, like 30s or 2m
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        interval: Duration,
+    },
+    /// Send pet movements and device changes to webhooks, until interrupted
+    Notify {
+        /// How long to wait between two polls, like 30s or 2m
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        interval: Duration,
+        /// URL to send every change to, on top of the `webhooks` of the configuration file
+        #[arg(long = "webhook", value_name = "URL")]
+        webhooks: Vec<String>,
+        /// Print the requests instead of sending them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Apply monitoring rules to pets and devices, and exit like a Nagios plugin
+    Check {
+        /// Battery percent under which a device is a warning, overriding the configuration file
+        #[arg(long)]
+        battery_warning: Option<f64>,
+        /// Battery percent under which a device is critical, overriding the configuration file
+        #[arg(long)]
+        battery_critical: Option<f64>,
+        /// How long a flap can stay disconnected, like 30m
+        #[arg(long, value_parser = humantime::parse_duration)]
+        flap_offline: Option<Duration>,
+        /// How long a pet can stay outside, like 12h
+        #[arg(long, value_parser = humantime::parse_duration)]
+        pet_outside: Option<Duration>,
+    },
+    /// Serve metrics of pets and devices to Prometheus, until interrupted
+    Exporter {
+        /// Address to serve `/metrics` on
+        #[arg(long, default_value = "127.0.0.1:9898")]
+        listen: SocketAddr,
+        /// How long to wait between two refreshes from the API, like 30s or 2m
+        #[arg(long, default_value = "1m", value_parser = humantime::parse_duration)]
+        interval: Duration,
+    },
+    /// Publish the state of pets and devices to an MQTT broker, until interrupted
+    Mqtt {
+        /// URL of the broker, with its credentials if any
+        #[arg(
+            long,
+            env = "SUREPET_MQTT_BROKER",
+            default_value = "mqtt://localhost:1883"
+        )]
+        broker: String,
+        /// First level of every topic
+        #[arg(long, default_value = "surepet")]
+        prefix: String,
+        /// How long to wait between two polls, like 30s or 2m
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        interval: Duration,
+        /// Lock flaps and move pets when asked on `<prefix>/.../set` topics
+        #[arg(long)]
+        commands: bool,
+        /// Announce pets and devices to Home Assistant, under this discovery prefix
+        #[arg(
+            long,
+            value_name = "PREFIX",
+            num_args = 0..=1,
+            default_missing_value = "homeassistant"
+        )]
+        discovery: Option<String>,
+    },
+    /// Manage a pet
+    Pet {
+        #[command(subcommand)]
+        command: PetCommands,
+    },
+    /// Control flaps
+    Flap {
+        #[command(subcommand)]
+        command: FlapCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum PetCommands {
+    /// Correct where a pet is, e.g. when the flap missed a passage
+    SetPosition {
+        /// Name of the pet
+        name: String,
+        /// Where the pet is
+        #[arg(value_enum)]
+        position: Position,
+        /// When the pet went there, like 10m, 14:12 or 2023-02-05 14:12 (defaults to now)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<FixedOffset>>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Position {
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Subcommand)]
+enum FlapCommands {
+    /// Change which ways pets can go through a flap
+    Lock {
+        /// Name of the flap
+        name: String,
+        /// Which ways to lock
+        #[arg(long, value_enum)]
+        mode: LockMode,
+    },
+    /// Manage the windows during which a flap keeps pets in
+    Curfew {
+        #[command(subcommand)]
+        command: CurfewCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CurfewCommands {
+    /// List the curfews of a flap
+    List {
+        /// Name of the flap
+        name: String,
+    },
+    /// Replace the curfews of a flap
+    Set {
+        /// Name of the flap
+        name: String,
+        /// Lock and unlock times, like 19:00-07:00 (repeat for several curfews)
+        #[arg(long = "window", required = true)]
+        windows: Vec<Curfew>,
+    },
+    /// Remove the curfews of a flap
+    Clear {
+        /// Name of the flap
+        name: String,
+    },
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = Cli::parse();
+    let check = matches!(args.command, Commands::Check { .. });
+
+    match run(args).await {
+        Ok(exit_code) => exit_code,
+        // Monitoring expects the reason on the standard output, and UNKNOWN whatever it is.
+        Err(error) if check => {
+            println!("SUREPET {} - {}", Severity::Unknown, error);
+            ExitCode::from(Severity::Unknown.exit_code())
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+async fn run(args: Cli) -> Result<ExitCode> {
+    let config = Config::load(&config_path()?)?;
+    let profile = config.profile(args.profile.as_deref())?;
+    let settings = profile
+        .map(|(_, profile)| profile)
+        .cloned()
+        .unwrap_or_default();
+    let base_url = args
+        .base_url
+        .or(settings.base_url.clone())
+        .or(config.base_url.clone())
+        .unwrap_or_else(|| BASE_URL.to_string());
+    let output = args.output.or(settings.output).unwrap_or_default();
+
+    if migrate_legacy_tokens()? {
+        eprintln!(
+            "Moved the tokens of ~/.surepet to {}",
+            state_dir()?.display()
+        );
+    }
+    let token_path = match profile {
+        Some((name, _)) => profile_token_path(name)?,
+        None => default_token_path()?,
+    };
+
+    let mut client = SurepetClient::new()?
+        .with_timeouts(args.connect_timeout, args.timeout)?
+        .with_retry_policy(RetryPolicy {
+            retries: args.retries,
+            ..RetryPolicy::default()
+        })
+        .with_base_url(base_url)
+        .with_strict(args.strict)
+        .with_token_cache(&token_path);
+    if let Some(credentials) = settings.credentials() {
+        client = client.with_credentials(credentials.email, credentials.password);
+    }
+    let session_command = matches!(
+        args.command,
+        Commands::Login { .. } | Commands::Logout { .. } | Commands::Whoami {}
+    );
+    if let Some(household) = args.household.or(settings.household.clone()) {
+        if !session_command {
+            let household = client.household(&household).await?;
+            client = client.with_household(household.id);
+        }
+    }
 
     match args.command {
-        Commands::Devices {} => {
-            for device in devices().await {
-                println!("{}", device.information());
+        Commands::Login {
+            email,
+            password_stdin,
+        } => {
+            let email = match email.or(settings.email).or(env::var("SUREPET_EMAIL").ok()) {
+                Some(email) => email,
+                None => prompt("Email: ")?,
+            };
+            let password = if password_stdin {
+                read_line()?
+            } else {
+                rpassword::prompt_password("Password: ")?
+            };
+            let client = client.with_credentials(&email, password);
+            let token = client.login().await?;
+            store_token(
+                &token_path,
+                &StoredToken::new(token, Some(email.clone()), client.base_url()),
+            )?;
+            println!("🔑 Logged in as {}", email);
+        }
+        Commands::Logout { revoke } => match read_token(&token_path)? {
+            Some(token) => {
+                // The local token goes away even when the API can't revoke it.
+                let revoked = if revoke {
+                    client.revoke_token(&token.token).await
+                } else {
+                    Ok(())
+                };
+                forget_token(&token_path)?;
+                println!("👋 Logged out");
+                revoked?;
             }
+            None => println!("👋 Not logged in"),
+        },
+        Commands::Whoami {} => {
+            let account = client.me().await?;
+            println!(
+                "👤 {} <{}> (user {})",
+                account.name, account.email, account.id
+            );
+            if let Some(token) = read_token(&token_path)? {
+                println!(
+                    "🔑 Token issued {} ago by {}",
+                    humantime::format_duration(Duration::from_secs(token.age().as_secs())),
+                    token.base_url
+                );
+            }
+        }
+        Commands::Households {} => {
+            let households: Vec<Box<Household>> = client
+                .households()
+                .await?
+                .into_iter()
+                .map(Box::new)
+                .collect();
+            print!("{}", render(output, &households)?);
         }
+        Commands::Devices {} => print!("{}", render(output, &client.devices().await?)?),
         Commands::Pets {} => {
-            for pet in pets().await {
-                println!("{}", pet.information());
+            let pets: Vec<Box<Pet>> = client.pets().await?.into_iter().map(Box::new).collect();
+            print!("{}", render(output, &pets)?);
+        }
+        Commands::Status {} => print!("{}", render(output, &client.status().await?.overview())?),
+        Commands::Timeline { pet, since, limit } => {
+            let pet = match pet {
+                Some(name) => Some(client.pet(&name).await?),
+                None => None,
+            };
+            let events = client.timeline(pet.as_ref(), since, limit).await?;
+            print!("{}", render(output, &events)?);
+        }
+        Commands::Watch { interval } => {
+            tokio::select! {
+                result = watch(&client, interval) => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Commands::Notify {
+            interval,
+            webhooks,
+            dry_run,
+        } => {
+            let webhooks: Vec<Webhook> = config
+                .webhooks
+                .iter()
+                .cloned()
+                .chain(webhooks.into_iter().map(|url| Webhook {
+                    url,
+                    ..Webhook::default()
+                }))
+                .collect();
+            if webhooks.is_empty() {
+                return Err(SurepetError::NotFound(format!(
+                    "any webhook in {}, nor --webhook",
+                    config_path()?.display()
+                )));
             }
+            tokio::select! {
+                result = notify(&client, &webhooks, interval, dry_run) => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Commands::Check {
+            battery_warning,
+            battery_critical,
+            flap_offline,
+            pet_outside,
+        } => {
+            let defaults = config.check.clone();
+            let rules = CheckRules {
+                battery_warning: battery_warning.unwrap_or(defaults.battery_warning),
+                battery_critical: battery_critical.unwrap_or(defaults.battery_critical),
+                flap_offline: flap_offline.unwrap_or(defaults.flap_offline),
+                pet_outside: pet_outside.unwrap_or(defaults.pet_outside),
+                ..defaults
+            };
+            let report = check(&client, &rules, &offline_flaps_path()?, now()).await?;
+            println!("{}", report);
+            return Ok(ExitCode::from(report.severity().exit_code()));
+        }
+        Commands::Exporter { listen, interval } => {
+            tokio::select! {
+                result = exporter::serve(&client, listen, interval) => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Commands::Mqtt {
+            broker,
+            prefix,
+            interval,
+            commands,
+            discovery,
+        } => {
+            let settings = MqttSettings {
+                broker,
+                prefix,
+                interval,
+                commands,
+                discovery,
+            };
+            tokio::select! {
+                result = mqtt::bridge(&client, &settings) => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Commands::Pet { command } => pet(&client, command).await?,
+        Commands::Flap { command } => flap(&client, command).await?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Poll pets and devices forever, printing what changed since the previous poll.
+///
+/// When the API fails, the error is printed and the wait doubles, see [`Poller`].
+async fn watch(client: &SurepetClient, interval: Duration) -> Result<()> {
+    let mut poller = Poller::new(client, interval);
+    let mut previous: Option<Snapshot> = None;
+
+    loop {
+        if let Some(status) = poller.next().await? {
+            let current = Snapshot::new(status.pets, &status.devices);
+            if let Some(previous) = &previous {
+                for change in previous.changes(&current) {
+                    println!("{}", change);
+                }
+            }
+            previous = Some(current);
+        }
+    }
+}
+
+async fn pet(client: &SurepetClient, command: PetCommands) -> Result<()> {
+    match command {
+        PetCommands::SetPosition {
+            name,
+            position,
+            since,
+        } => {
+            let pet = client.pet(&name).await?;
+            let position = match position {
+                Position::Inside => PetPosition::Inside,
+                Position::Outside => PetPosition::Outside,
+            };
+            let pet = client
+                .set_position(&pet, position, since.unwrap_or_else(now))
+                .await?;
+            println!("{}", pet.information());
+        }
+    }
+    Ok(())
+}
+
+/// Ask for a value on the terminal.
+fn prompt(message: &str) -> Result<String> {
+    eprint!("{}", message);
+    io::stderr().flush()?;
+    read_line()
+}
+
+/// A line of the standard input, without its line break.
+fn read_line() -> Result<String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn parse_since(value: &str) -> std::result::Result<DateTime<FixedOffset>, String> {
+    parse_time(value, now())
+}
+
+async fn flap(client: &SurepetClient, command: FlapCommands) -> Result<()> {
+    match command {
+        FlapCommands::Lock { name, mode } => {
+            let flap = client.flap(&name).await?;
+            let lock_mode = client.set_lock_mode(&flap, mode).await?;
+            println!(
+                "{} {} is now {}",
+                lock_icon(lock_mode),
+                flap.name,
+                lock_mode.description()
+            );
+        }
+        FlapCommands::Curfew { command } => {
+            let (flap, curfews) = match command {
+                CurfewCommands::List { name } => {
+                    let flap = client.flap(&name).await?;
+                    let curfews = client.curfews(&flap).await?;
+                    (flap, curfews)
+                }
+                CurfewCommands::Set { name, windows } => {
+                    let flap = client.flap(&name).await?;
+                    let curfews = client.set_curfews(&flap, &windows).await?;
+                    (flap, curfews)
+                }
+                CurfewCommands::Clear { name } => {
+                    let flap = client.flap(&name).await?;
+                    let curfews = client.clear_curfews(&flap).await?;
+                    (flap, curfews)
+                }
+            };
+            print_curfews(&flap.name, &curfews);
         }
     }
+    Ok(())
+}
+
+fn lock_icon(lock_mode: LockMode) -> &'static str {
+    match lock_mode {
+        LockMode::None => "🔓",
+        _ => "🔒",
+    }
+}
+
+fn print_curfews(name: &str, curfews: &[Curfew]) {
+    if curfews.is_empty() {
+        println!("☀️ {} has no curfew", name);
+    }
+    for curfew in curfews {
+        if curfew.enabled {
+            println!("🌙 {} keeps pets in from {}", name, curfew);
+        } else {
+            println!("💤 {} curfew {} is disabled", name, curfew);
+        }
+    }
+}
+
+/// Process exit code for each kind of failure, so scripts can react to them.
+fn exit_code(error: &SurepetError) -> u8 {
+    match error {
+        SurepetError::Network(_) => 2,
+        SurepetError::Http { .. } => 3,
+        SurepetError::Unauthorized => 4,
+        SurepetError::Decode { .. } => 5,
+        SurepetError::MissingCredentials(_) => 6,
+        SurepetError::UnknownProduct(_) => 7,
+        SurepetError::Io(_) => 8,
+        SurepetError::Config { .. } => 9,
+        SurepetError::Encode(_) => 10,
+        SurepetError::NotFound(_) => 11,
+        SurepetError::Unacknowledged(_) => 12,
+        SurepetError::InvalidCurfew(_) => 13,
+        SurepetError::PasswordCommand { .. } => 14,
+        SurepetError::Mqtt(_) => 15,
+    }
 }
@@ -0,0 +1,292 @@
+//! [Home Assistant MQTT discovery](https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery):
+//! configurations that make Home Assistant create entities reading the topics of the bridge.
+
+use serde_json::{json, Value};
+
+use super::{availability_topic, device_topic, lock_topic, pet_topic, Message, MqttSettings};
+use crate::entities::shared::{Information, Record};
+use crate::entities::status::Status;
+
+/// Lock modes Home Assistant can ask for: a curfew can't be set this way.
+const LOCK_MODES: [&str; 4] = ["none", "in", "out", "both"];
+
+/// Retained configurations of the entities of pets, hubs and flaps.
+///
+/// Unique IDs are made of the Sure Petcare IDs, so that entities survive renames.
+pub(super) fn messages(
+    discovery_prefix: &str,
+    settings: &MqttSettings,
+    status: &Status,
+) -> Vec<Message> {
+    let prefix = &settings.prefix;
+    let mut messages = vec![];
+
+    for record in status.pets.iter().map(|pet| pet.record()) {
+        messages.extend(pet_entity(discovery_prefix, prefix, &record));
+    }
+    for record in status.devices.iter().map(|device| device.record()) {
+        let Some(id) = record.id else { continue };
+        let config = Config::new(discovery_prefix, prefix, &record, id);
+        let state_topic = format!("{}/state", device_topic(prefix, &record.name));
+
+        if matches!(record.kind, "hub" | "flap") {
+            messages.push(config.entity(
+                "binary_sensor",
+                "connectivity",
+                json!({
+                    "name": "Connectivity",
+                    "device_class": "connectivity",
+                    "entity_category": "diagnostic",
+                    "state_topic": state_topic,
+                    "value_template": "{{ 'ON' if value_json.online else 'OFF' }}",
+                }),
+            ));
+        }
+        if record.kind != "flap" {
+            continue;
+        }
+
+        let battery_topic = format!("{}/battery", device_topic(prefix, &record.name));
+        messages.push(config.entity(
+            "sensor",
+            "battery",
+            json!({
+                "name": "Battery",
+                "device_class": "battery",
+                "entity_category": "diagnostic",
+                "state_class": "measurement",
+                "unit_of_measurement": "%",
+                "state_topic": battery_topic,
+                "value_template": "{{ value_json.percent }}",
+            }),
+        ));
+        messages.push(config.entity(
+            "sensor",
+            "battery_voltage",
+            json!({
+                "name": "Battery voltage",
+                "device_class": "voltage",
+                "entity_category": "diagnostic",
+                "state_class": "measurement",
+                "unit_of_measurement": "V",
+                "state_topic": battery_topic,
+                "value_template": "{{ value_json.voltage }}",
+            }),
+        ));
+        // Without the commands, the lock mode could be shown but not changed.
+        if settings.commands {
+            messages.push(config.entity(
+                "select",
+                "lock_mode",
+                json!({
+                    "name": "Lock mode",
+                    "options": LOCK_MODES,
+                    "state_topic": state_topic,
+                    // Curfews and unknown modes aren't options: show no option for them.
+                    "value_template": format!(
+                        "{{{{ value_json.lock_mode if value_json.lock_mode in {} else None }}}}",
+                        json!(LOCK_MODES)
+                    ),
+                    "command_topic": format!("{}/set", lock_topic(prefix, &record.name)),
+                }),
+            ));
+        }
+    }
+    messages
+}
+
+/// The configurations that weren't announced yet, remembering them as announced.
+///
+/// Home Assistant processes every configuration it receives again, so unchanged ones are not
+/// published at every poll.
+pub(super) fn unannounced(announced: &mut Vec<Message>, configs: Vec<Message>) -> Vec<Message> {
+    let unannounced = configs
+        .iter()
+        .filter(|config| !announced.contains(config))
+        .cloned()
+        .collect();
+    *announced = configs;
+    unannounced
+}
+
+fn pet_entity(discovery_prefix: &str, prefix: &str, record: &Record) -> Option<Message> {
+    let config = Config::new(discovery_prefix, prefix, record, record.id?);
+
+    Some(config.entity(
+        "device_tracker",
+        "position",
+        json!({
+            "name": "Position",
+            "state_topic": format!("{}/state", pet_topic(prefix, &record.name)),
+            "value_template": "{{ 'home' if value_json.position == 'inside' else 'not_home' }}",
+            "payload_home": "home",
+            "payload_not_home": "not_home",
+        }),
+    ))
+}
+
+/// What the entities of a pet or a device share.
+struct Config<'a> {
+    discovery_prefix: &'a str,
+    /// Like `surepet_flap_456`.
+    node: String,
+    availability_topic: String,
+    device: Value,
+}
+
+impl<'a> Config<'a> {
+    fn new(discovery_prefix: &'a str, prefix: &str, record: &Record, id: u64) -> Self {
+        let node = format!("surepet_{}_{}", record.kind, id);
+        Config {
+            discovery_prefix,
+            device: json!({
+                "identifiers": [node],
+                "name": record.name,
+                "manufacturer": "Sure Petcare",
+                "model": model(record.kind),
+            }),
+            node,
+            availability_topic: availability_topic(prefix),
+        }
+    }
+
+    /// Configuration of an entity, completed with what all entities share.
+    fn entity(&self, component: &str, object: &str, mut config: Value) -> Message {
+        let unique_id = format!("{}_{}", self.node, object);
+        if let Value::Object(fields) = &mut config {
+            fields.insert("unique_id".to_string(), json!(unique_id));
+            fields.insert("object_id".to_string(), json!(unique_id));
+            fields.insert(
+                "availability_topic".to_string(),
+                json!(self.availability_topic),
+            );
+            fields.insert("device".to_string(), self.device.clone());
+        }
+        Message::retained(
+            format!(
+                "{}/{}/{}/{}/config",
+                self.discovery_prefix, component, self.node, object
+            ),
+            config,
+        )
+    }
+}
+
+fn model(kind: &str) -> &'static str {
+    match kind {
+        "hub" => "Hub",
+        "flap" => "Flap",
+        _ => "Pet",
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::entities::test_support;
+    #[cfg(test)]
+    use std::time::Duration;
+
+    #[cfg(test)]
+    fn settings(commands: bool) -> MqttSettings {
+        MqttSettings {
+            broker: "mqtt://localhost".to_string(),
+            prefix: "surepet".to_string(),
+            interval: Duration::from_secs(30),
+            commands,
+            discovery: Some("homeassistant".to_string()),
+        }
+    }
+
+    #[cfg(test)]
+    fn config(messages: &[Message], topic: &str) -> Value {
+        let message = messages
+            .iter()
+            .find(|message| message.topic == topic)
+            .unwrap_or_else(|| panic!("No message on {}", topic));
+        assert!(message.retain);
+        serde_json::from_str(&message.payload).unwrap()
+    }
+
+    #[test]
+    fn it_announces_pets_hubs_and_flaps() {
+        let messages = messages("homeassistant", &settings(false), &test_support::status());
+
+        let topics: Vec<&str> = messages
+            .iter()
+            .map(|message| message.topic.as_str())
+            .collect();
+        assert_eq!(
+            topics,
+            vec![
+                "homeassistant/device_tracker/surepet_pet_789/position/config",
+                "homeassistant/binary_sensor/surepet_hub_123/connectivity/config",
+                "homeassistant/binary_sensor/surepet_flap_456/connectivity/config",
+                "homeassistant/sensor/surepet_flap_456/battery/config",
+                "homeassistant/sensor/surepet_flap_456/battery_voltage/config",
+            ]
+        );
+
+        let tracker = config(&messages, topics[0]);
+        assert_eq!(tracker["unique_id"], "surepet_pet_789_position");
+        assert_eq!(tracker["state_topic"], "surepet/pet/Garfield/state");
+        assert_eq!(tracker["availability_topic"], "surepet/availability");
+        assert_eq!(tracker["device"]["identifiers"], json!(["surepet_pet_789"]));
+        assert_eq!(tracker["device"]["name"], "Garfield");
+
+        let battery = config(&messages, topics[3]);
+        assert_eq!(battery["unique_id"], "surepet_flap_456_battery");
+        assert_eq!(battery["state_topic"], "surepet/device/Buanderie/battery");
+        assert_eq!(battery["unit_of_measurement"], "%");
+    }
+
+    #[test]
+    fn it_announces_lock_modes_with_commands() {
+        let messages = messages("homeassistant", &settings(true), &test_support::status());
+
+        let select = config(
+            &messages,
+            "homeassistant/select/surepet_flap_456/lock_mode/config",
+        );
+        assert_eq!(select["command_topic"], "surepet/device/Buanderie/lock/set");
+        assert_eq!(select["options"], json!(["none", "in", "out", "both"]));
+        assert_eq!(
+            select["value_template"],
+            r#"{{ value_json.lock_mode if value_json.lock_mode in ["none","in","out","both"] else None }}"#
+        );
+    }
+
+    #[test]
+    fn it_announces_only_new_configurations() {
+        let mut announced = vec![];
+        let mut status = test_support::status();
+
+        let first = unannounced(
+            &mut announced,
+            messages("homeassistant", &settings(false), &status),
+        );
+        assert_eq!(first.len(), 5);
+        assert_eq!(
+            unannounced(
+                &mut announced,
+                messages("homeassistant", &settings(false), &status)
+            ),
+            vec![]
+        );
+
+        status.pets[0].name = "Arlene".to_string();
+        let renamed = unannounced(
+            &mut announced,
+            messages("homeassistant", &settings(false), &status),
+        );
+        let topics: Vec<&str> = renamed
+            .iter()
+            .map(|message| message.topic.as_str())
+            .collect();
+        assert_eq!(
+            topics,
+            vec!["homeassistant/device_tracker/surepet_pet_789/position/config"]
+        );
+    }
+}
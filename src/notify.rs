@@ -0,0 +1,384 @@
+use std::env;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::api::send_retrying;
+use crate::client::{http_client, RetryPolicy, SurepetClient, CONNECT_TIMEOUT};
+use crate::config::{Webhook, WebhookFormat};
+use crate::error::{Result, SurepetError};
+use crate::watch::{Change, Poller, Snapshot};
+
+/// Header holding the signature of the body, when the webhook has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Surepet-Signature";
+/// How long to wait for a webhook to answer.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request to send to a webhook.
+#[derive(Debug, PartialEq)]
+pub struct Notification {
+    pub url: String,
+    pub content_type: &'static str,
+    pub body: String,
+    /// Like `sha256=<hex>`: the HMAC-SHA256 of the body.
+    pub signature: Option<String>,
+}
+
+impl Notification {
+    /// What would be sent, as an HTTP request.
+    pub fn describe(&self) -> String {
+        let mut request = format!("POST {}\nContent-Type: {}\n", self.url, self.content_type);
+        if let Some(signature) = &self.signature {
+            request.push_str(&format!("{}: {}\n", SIGNATURE_HEADER, signature));
+        }
+        format!("{}\n{}\n", request, self.body)
+    }
+}
+
+/// A webhook, along with the key to sign its bodies with.
+#[derive(Debug)]
+struct Target {
+    webhook: Webhook,
+    secret: Option<String>,
+}
+
+/// Poll pets and devices forever, sending what changed since the previous poll to webhooks.
+///
+/// Webhooks are called with their own timeouts, and failed requests are retried as the client
+/// retry policy says, then reported. With `dry_run`, requests are printed instead of sent.
+pub async fn notify(
+    client: &SurepetClient,
+    webhooks: &[Webhook],
+    interval: Duration,
+    dry_run: bool,
+) -> Result<()> {
+    let targets = webhooks
+        .iter()
+        .map(|webhook| {
+            let secret = match &webhook.secret_env {
+                Some(variable) => Some(
+                    env::var(variable)
+                        .map_err(|_| SurepetError::MissingCredentials(variable.clone()))?,
+                ),
+                None => None,
+            };
+            Ok(Target {
+                webhook: webhook.clone(),
+                secret,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let http = http_client(CONNECT_TIMEOUT, WEBHOOK_TIMEOUT)?;
+    let mut poller = Poller::new(client, interval);
+    let mut previous: Option<Snapshot> = None;
+
+    loop {
+        if let Some(status) = poller.next().await? {
+            let current = Snapshot::new(status.pets, &status.devices);
+            if let Some(previous) = &previous {
+                for change in previous.changes(&current) {
+                    for notification in notifications(&targets, &change) {
+                        if dry_run {
+                            print!("{}", notification.describe());
+                        } else if let Err(error) =
+                            send(&http, client.retry_policy, &notification).await
+                        {
+                            eprintln!("{}", error);
+                        }
+                    }
+                }
+            }
+            previous = Some(current);
+        }
+    }
+}
+
+/// Requests telling the webhooks that want it about a change.
+fn notifications(targets: &[Target], change: &Change) -> Vec<Notification> {
+    targets
+        .iter()
+        .filter(|target| target.webhook.wants(change.kind()))
+        .map(|target| {
+            let format = target.webhook.format;
+            let body = match (&target.webhook.template, format) {
+                (Some(template), _) => render(template, change, format),
+                (None, WebhookFormat::Json) => {
+                    let mut payload = change.payload();
+                    payload["message"] = Value::String(change.to_string());
+                    payload.to_string()
+                }
+                (None, WebhookFormat::Text) => change.to_string(),
+            };
+            Notification {
+                url: target.webhook.url.clone(),
+                content_type: format.content_type(),
+                signature: target.secret.as_ref().map(|secret| sign(secret, &body)),
+                body,
+            }
+        })
+        .collect()
+}
+
+/// Fill a template with the fields of a change, escaped to fit in strings of JSON templates.
+fn render(template: &str, change: &Change, format: WebhookFormat) -> String {
+    let payload = change.payload();
+    let field = |name: &str| match &payload[name] {
+        Value::String(value) => value.clone(),
+        _ => String::new(),
+    };
+    let escape = |value: &str| match format {
+        WebhookFormat::Json => {
+            let quoted = Value::String(value.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        }
+        WebhookFormat::Text => value.to_string(),
+    };
+
+    let value = |placeholder: &str| match placeholder {
+        "event" | "name" | "position" | "since" => Some(field(placeholder)),
+        "message" => Some(change.to_string()),
+        _ => None,
+    };
+
+    // In a single pass, so that values looking like placeholders are left as they are.
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after
+            .find('}')
+            .and_then(|end| Some((end, value(&after[..end])?)));
+        match placeholder {
+            Some((end, value)) => {
+                rendered.push_str(&escape(&value));
+                rest = &after[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// POST a notification, retrying on network errors, too many requests and server errors.
+async fn send(
+    http: &reqwest::Client,
+    policy: RetryPolicy,
+    notification: &Notification,
+) -> Result<()> {
+    let response = send_retrying(policy, true, || {
+        let request = http
+            .post(&notification.url)
+            .header("Content-Type", notification.content_type)
+            .body(notification.body.clone());
+        match &notification.signature {
+            Some(signature) => request.header(SIGNATURE_HEADER, signature),
+            None => request,
+        }
+    })
+    .await?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(SurepetError::Http {
+            status: response.status(),
+            body: response.text().await?,
+        })
+    }
+}
+
+#[cfg(test)]
+use mockito;
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::mocked_client;
+    #[cfg(test)]
+    use crate::entities::pet::PetPosition;
+    #[cfg(test)]
+    use crate::watch::ChangeKind;
+    #[cfg(test)]
+    use mockito::{mock, Matcher};
+
+    #[cfg(test)]
+    const HOOK_PATH: &str = "/hooks/cats";
+
+    #[cfg(test)]
+    fn target(events: Vec<ChangeKind>, template: Option<&str>, secret: Option<&str>) -> Target {
+        Target {
+            webhook: Webhook {
+                url: format!("{}{}", mockito::server_url(), HOOK_PATH),
+                events,
+                format: WebhookFormat::Json,
+                template: template.map(str::to_string),
+                secret_env: None,
+            },
+            secret: secret.map(str::to_string),
+        }
+    }
+
+    #[cfg(test)]
+    fn moved() -> Change {
+        moved_pet("Garfield")
+    }
+
+    #[cfg(test)]
+    fn moved_pet(name: &str) -> Change {
+        Change::PetMoved {
+            name: name.to_string(),
+            position: PetPosition::Inside,
+            since: "2023-01-01T14:02:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn it_sends_the_change_as_json_by_default() {
+        let notifications = notifications(&[target(vec![], None, None)], &moved());
+
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].content_type, "application/json");
+        assert_eq!(notifications[0].signature, None);
+        let body: Value = serde_json::from_str(&notifications[0].body).unwrap();
+        assert_eq!(body["event"], "pet_moved");
+        assert_eq!(body["name"], "Garfield");
+        assert_eq!(body["position"], "inside");
+        assert_eq!(body["since"], "2023-01-01T14:02:00+00:00");
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("Garfield went inside"));
+    }
+
+    #[test]
+    fn it_only_sends_the_events_a_webhook_wants() {
+        let targets = [
+            target(vec![ChangeKind::LowBattery], None, None),
+            target(vec![ChangeKind::PetMoved, ChangeKind::Offline], None, None),
+        ];
+
+        assert_eq!(notifications(&targets, &moved()).len(), 1);
+        assert!(notifications(&targets, &Change::Online("Hub".to_string())).is_empty());
+    }
+
+    #[cfg(test)]
+    fn text_target(template: Option<&str>) -> Target {
+        let mut target = target(vec![], template, None);
+        target.webhook.format = WebhookFormat::Text;
+        target
+    }
+
+    #[test]
+    fn it_fills_templates() {
+        let targets = [
+            target(
+                vec![],
+                Some(r#"{"text": "{name} is {position}: {message}"}"#),
+                None,
+            ),
+            text_target(Some("{name} went {position}")),
+        ];
+        let change = moved_pet("Garfield \"the cat\"");
+
+        let notifications = notifications(&targets, &change);
+
+        assert_eq!(notifications[0].content_type, "application/json");
+        let body: Value = serde_json::from_str(&notifications[0].body).unwrap();
+        assert!(body["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Garfield \"the cat\" is inside: "));
+        assert_eq!(notifications[1].content_type, "text/plain; charset=utf-8");
+        assert_eq!(notifications[1].body, r#"Garfield "the cat" went inside"#);
+    }
+
+    #[test]
+    fn it_fills_templates_once() {
+        let notifications = notifications(
+            &[text_target(Some("{name} went {position} {unknown}"))],
+            &moved_pet("{message} {position}"),
+        );
+
+        assert_eq!(
+            notifications[0].body,
+            "{message} {position} went inside {unknown}"
+        );
+    }
+
+    #[test]
+    fn it_sends_the_summary_as_text() {
+        let notifications = notifications(&[text_target(None)], &moved());
+
+        assert_eq!(notifications[0].content_type, "text/plain; charset=utf-8");
+        assert_eq!(notifications[0].body, "🏠 Garfield went inside at 14:02");
+    }
+
+    #[test]
+    fn it_signs_bodies() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        let notifications = notifications(&[target(vec![], None, Some("key"))], &moved());
+        assert_eq!(
+            notifications[0].signature,
+            Some(sign("key", &notifications[0].body))
+        );
+    }
+
+    #[test]
+    fn it_retries_webhooks() {
+        let notification = Notification {
+            url: format!("{}{}", mockito::server_url(), HOOK_PATH),
+            content_type: "application/json",
+            body: r#"{"event": "online"}"#.to_string(),
+            signature: Some("sha256=abc".to_string()),
+        };
+        let _unavailable = mock("POST", HOOK_PATH).with_status(502).expect(1).create();
+        let delivered = mock("POST", HOOK_PATH)
+            .match_header("x-surepet-signature", "sha256=abc")
+            .match_body(Matcher::JsonString(notification.body.clone()))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        tokio_test::block_on(send(
+            &reqwest::Client::new(),
+            mocked_client().retry_policy,
+            &notification,
+        ))
+        .unwrap();
+        delivered.assert();
+    }
+
+    #[test]
+    fn it_describes_dry_runs() {
+        let notification = Notification {
+            url: "https://example.com/hook".to_string(),
+            content_type: "application/json",
+            body: "{}".to_string(),
+            signature: Some("sha256=abc".to_string()),
+        };
+
+        assert_eq!(
+            notification.describe(),
+            "POST https://example.com/hook\nContent-Type: application/json\n\
+             X-Surepet-Signature: sha256=abc\n\n{}\n"
+        );
+    }
+}
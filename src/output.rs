@@ -0,0 +1,250 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use serde_json::Value;
+
+use crate::entities::event::{Event, EventRecord};
+use crate::entities::shared::{Columns, Information, Record};
+use crate::error::{Result, SurepetError};
+
+/// How to print pets and devices.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// One JSON array
+    Json,
+    /// One YAML sequence
+    Yaml,
+    /// Comma separated values, with a header
+    Csv,
+    /// Aligned columns, with a header
+    Table,
+    /// Human friendly summaries
+    #[default]
+    Text,
+}
+
+/// What can be rendered: a summary for humans, and a record for machines.
+pub trait Renderable {
+    type Record: Columns;
+
+    fn summary(&self) -> String;
+
+    fn to_record(&self) -> Self::Record;
+}
+
+impl<T: Information + ?Sized> Renderable for Box<T> {
+    type Record = Record;
+
+    fn summary(&self) -> String {
+        self.information()
+    }
+
+    fn to_record(&self) -> Record {
+        self.record()
+    }
+}
+
+impl Renderable for Event {
+    type Record = EventRecord;
+
+    fn summary(&self) -> String {
+        self.information()
+    }
+
+    fn to_record(&self) -> EventRecord {
+        self.record()
+    }
+}
+
+/// Render entities or events in the given format.
+pub fn render<R: Renderable>(format: OutputFormat, items: &[R]) -> Result<String> {
+    if format == OutputFormat::Text {
+        return Ok(items
+            .iter()
+            .map(|item| format!("{}\n", item.summary()))
+            .collect());
+    }
+
+    let records: Vec<R::Record> = items.iter().map(Renderable::to_record).collect();
+    match format {
+        OutputFormat::Json => Ok(format!(
+            "{}\n",
+            encode(serde_json::to_string_pretty(&records))?
+        )),
+        OutputFormat::Yaml => encode(serde_yaml::to_string(&records)),
+        OutputFormat::Csv => csv(&records),
+        OutputFormat::Table => table(&records),
+        OutputFormat::Text => unreachable!(),
+    }
+}
+
+fn encode<E: std::fmt::Display>(result: std::result::Result<String, E>) -> Result<String> {
+    result.map_err(|error| SurepetError::Encode(error.to_string()))
+}
+
+/// One column per name of [`Columns::COLUMNS`], empty for the fields left out of a record.
+fn csv<R: Columns>(records: &[R]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(R::COLUMNS)
+        .map_err(|error| SurepetError::Encode(error.to_string()))?;
+    for record in records {
+        let fields = serde_json::to_value(record)
+            .map_err(|error| SurepetError::Encode(error.to_string()))?;
+        writer
+            .write_record(R::COLUMNS.iter().map(|column| cell(&fields[column])))
+            .map_err(|error| SurepetError::Encode(error.to_string()))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|error| SurepetError::Encode(error.to_string()))?;
+    encode(String::from_utf8(bytes))
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Aligned columns, leaving out the ones that are empty for every record.
+fn table<R: Columns>(records: &[R]) -> Result<String> {
+    let csv = csv(records)?;
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let header = reader
+        .headers()
+        .map_err(|error| SurepetError::Encode(error.to_string()))?
+        .clone();
+    let rows = reader
+        .records()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|error| SurepetError::Encode(error.to_string()))?;
+
+    let columns: Vec<usize> = (0..header.len())
+        .filter(|&column| rows.iter().any(|row| !row[column].is_empty()))
+        .collect();
+    let lines: Vec<Vec<String>> = [header.iter().map(str::to_uppercase).collect()]
+        .into_iter()
+        .chain(
+            rows.iter()
+                .map(|row| row.iter().map(str::to_string).collect()),
+        )
+        .map(|line: Vec<String>| columns.iter().map(|&column| line[column].clone()).collect())
+        .collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|column| {
+            lines
+                .iter()
+                .map(|line| line[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    Ok(lines
+        .iter()
+        .map(|line| {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect())
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::entities::{
+        event::EventKind,
+        flap::{Flap, LockMode},
+        pet::PetPosition,
+        test_support,
+    };
+
+    #[cfg(test)]
+    fn devices() -> Vec<Box<dyn Information>> {
+        vec![
+            Box::new(test_support::hub()),
+            Box::new(Flap {
+                online: false,
+                lock_mode: LockMode::Both,
+                ..test_support::flap()
+            }),
+        ]
+    }
+
+    #[test]
+    fn it_renders_text() {
+        assert_eq!(
+            render(OutputFormat::Text, &devices()).unwrap(),
+            "✅ Hub is online\n❌ Buanderie is disconnected\n"
+        );
+    }
+
+    #[test]
+    fn it_renders_json() {
+        let rendered: Value =
+            serde_json::from_str(&render(OutputFormat::Json, &devices()).unwrap()).unwrap();
+
+        assert_eq!(rendered[0]["kind"], "hub");
+        assert!(rendered[0].get("battery_voltage").is_none());
+        assert_eq!(rendered[1]["name"], "Buanderie");
+        assert_eq!(rendered[1]["online"], false);
+        assert_eq!(rendered[1]["battery_voltage"], 5.6);
+    }
+
+    #[test]
+    fn it_leaves_out_empty_fields_of_yaml() {
+        let rendered = render(OutputFormat::Yaml, &devices()).unwrap();
+
+        assert!(rendered.starts_with("- kind: hub\n  name: Hub\n"));
+        assert!(!rendered.contains("null"));
+    }
+
+    #[test]
+    fn it_renders_csv() {
+        assert_eq!(
+            render(OutputFormat::Csv, &devices()).unwrap(),
+            "kind,name,online,battery_voltage,battery_percent,position,position_since,duration,\
+             first_bowl_weight,second_bowl_weight,water_weight,product_id,lock_mode,curfew,household_id,id\n\
+             hub,Hub,true,,,,,,,,,,,,42,123\n\
+             flap,Buanderie,false,5.6,50.0,,,,,,,,both,,42,456\n"
+        );
+    }
+
+    #[test]
+    fn it_renders_events_with_their_own_columns() {
+        let events = vec![Event {
+            id: 1,
+            at: "2023-02-05T14:02:00+01:00".parse().unwrap(),
+            kind: EventKind::Movement {
+                pet: "Garfield".to_string(),
+                position: PetPosition::Outside,
+                device: "Buanderie".to_string(),
+            },
+        }];
+
+        assert_eq!(
+            render(OutputFormat::Csv, &events).unwrap(),
+            "time,event,name,position,device,weight_change,description\n\
+             2023-02-05T14:02:00+01:00,movement,Garfield,outside,Buanderie,,\n"
+        );
+    }
+
+    #[test]
+    fn it_renders_table() {
+        assert_eq!(
+            render(OutputFormat::Table, &devices()).unwrap(),
+            "KIND  NAME       ONLINE  BATTERY_VOLTAGE  BATTERY_PERCENT  LOCK_MODE  HOUSEHOLD_ID  ID\n\
+             hub   Hub        true                                                 42            123\n\
+             flap  Buanderie  false   5.6              50.0             both       42            456\n"
+        );
+    }
+}
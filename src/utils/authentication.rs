@@ -1,33 +1,460 @@
-#[cfg(not(test))]
 use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[cfg(not(test))]
-use crate::api::login::login;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-#[cfg(not(test))]
-const TOKEN_PATH: &str = ".surepet/token";
+use crate::client::BASE_URL;
+use crate::error::Result;
+use crate::utils::paths::{legacy_dir, state_dir, surepet_home};
 
-#[cfg(not(test))]
-/// Read token from file, or query API to get a new one.
-pub async fn bearer_token() -> String {
-    match home::home_dir() {
-        Some(home) => {
-            let full_path = format!("/{}/{}", home.display(), TOKEN_PATH);
-            let token_path = std::path::Path::new(&full_path);
+const TOKEN_PATH: &str = "token";
+const PROFILE_TOKENS_PATH: &str = "tokens";
 
-            if token_path.exists() {
-                match fs::read_to_string(token_path) {
-                    Ok(token) => return token,
-                    _ => panic!("Cannot read token from file"),
-                }
-            } else {
-                let token = login().await;
+/// A token, along with what it was issued for.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct StoredToken {
+    pub token: String,
+    pub issued_at: DateTime<Utc>,
+    /// Email of the account the token belongs to, when known.
+    pub email: Option<String>,
+    /// API the token was issued by.
+    pub base_url: String,
+}
 
-                fs::create_dir_all(token_path.parent().unwrap()).unwrap();
-                fs::write(token_path, &token).expect("Unable to write file");
-                return token;
-            }
+impl StoredToken {
+    /// A token issued now.
+    pub fn new(
+        token: impl Into<String>,
+        email: Option<String>,
+        base_url: impl Into<String>,
+    ) -> Self {
+        StoredToken {
+            token: token.into(),
+            issued_at: Utc::now(),
+            email,
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Time elapsed since the token was issued.
+    pub fn age(&self) -> Duration {
+        Utc::now()
+            .signed_duration_since(self.issued_at)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+/// Where the CLI caches the token between two invocations.
+pub fn default_token_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join(TOKEN_PATH))
+}
+
+/// Where the CLI caches the token of the given profile, so that each account keeps its own.
+pub fn profile_token_path(profile: &str) -> Result<PathBuf> {
+    Ok(state_dir()?.join(PROFILE_TOKENS_PATH).join(profile))
+}
+
+/// Move the tokens of the legacy `~/.surepet` directory to the state directory.
+///
+/// This only happens on the first use of the state directory: once it exists, the legacy
+/// directory is left alone. Tokens saved in the plain format of older versions are stored with
+/// their metadata, and the legacy directory is removed once empty. Returns whether anything
+/// moved.
+pub fn migrate_legacy_tokens() -> Result<bool> {
+    match legacy_dir()? {
+        Some(legacy_dir) => migrate_tokens(&legacy_dir, &state_dir()?),
+        None => Ok(false),
+    }
+}
+
+fn migrate_tokens(from: &Path, to: &Path) -> Result<bool> {
+    if to.exists() || !from.is_dir() {
+        return Ok(false);
+    }
+
+    let mut names = vec![PathBuf::from(TOKEN_PATH)];
+    if let Ok(profiles) = fs::read_dir(from.join(PROFILE_TOKENS_PATH)) {
+        for profile in profiles {
+            names.push(Path::new(PROFILE_TOKENS_PATH).join(profile?.file_name()));
+        }
+    }
+
+    let mut migrated = false;
+    for name in names {
+        let source = from.join(&name);
+        if source.is_file() {
+            store_token(&to.join(&name), &legacy_token(&source)?)?;
+            fs::remove_file(&source)?;
+            migrated = true;
+        }
+    }
+    // Left alone when they still hold something.
+    let _ = fs::remove_dir(from.join(PROFILE_TOKENS_PATH));
+    let _ = fs::remove_dir(from);
+    Ok(migrated)
+}
+
+/// A token of the legacy directory.
+///
+/// Older versions saved the bare token: it was issued by the default API, at the latest when the
+/// file was written.
+fn legacy_token(path: &Path) -> Result<StoredToken> {
+    let content = fs::read_to_string(path)?;
+    if let Ok(token) = serde_json::from_str(&content) {
+        return Ok(token);
+    }
+    Ok(StoredToken {
+        token: content.trim().to_string(),
+        issued_at: fs::metadata(path)?.modified()?.into(),
+        email: None,
+        base_url: BASE_URL.to_string(),
+    })
+}
+
+/// Read token from file, if there is a usable one.
+///
+/// Tokens that others could read or replace (file or directory open to the group or others) are
+/// ignored with a warning, and so are tokens saved in the plain format of older versions: a new
+/// token will replace them.
+pub fn read_token(token_path: &Path) -> Result<Option<StoredToken>> {
+    if !token_path.exists() {
+        return Ok(None);
+    }
+    if let Some(path) = too_open(token_path)? {
+        eprintln!(
+            "Warning: ignoring the token of {}, as {} is accessible to other users",
+            token_path.display(),
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    match serde_json::from_str(&fs::read_to_string(token_path)?) {
+        Ok(token) => Ok(Some(token)),
+        Err(_) => {
+            eprintln!(
+                "Warning: ignoring the token of {}, saved by an older version: please log in again",
+                token_path.display()
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Persist token to file, only readable by the current user.
+pub fn store_token(token_path: &Path, token: &StoredToken) -> Result<()> {
+    let content = serde_json::to_string(token)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+    Ok(write_private(token_path, &content)?)
+}
+
+/// Write a file only readable by the current user, in a directory only they can open.
+///
+/// The content is written to a temporary file first, then renamed, so that a concurrent read
+/// never gets half of it. Nothing is written, with a warning, in a directory others can access
+/// that the tool can't close.
+pub(crate) fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    create_private_dir(directory)?;
+    if !restrict_dir(directory)? {
+        eprintln!(
+            "Warning: not saving {}, as {} is accessible to other users",
+            path.display(),
+            directory.display()
+        );
+        return Ok(());
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary_path = directory.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = write_private_file(&temporary_path, content)
+        .and_then(|()| fs::rename(&temporary_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+    result
+}
+
+/// Remove token file, if there is one.
+pub fn forget_token(token_path: &Path) -> Result<()> {
+    if token_path.exists() {
+        fs::remove_file(token_path)?;
+    }
+    Ok(())
+}
+
+/// The token file, or its directory, if other users can access it.
+#[cfg(unix)]
+fn too_open(token_path: &Path) -> Result<Option<PathBuf>> {
+    for path in [Some(token_path), token_path.parent()]
+        .into_iter()
+        .flatten()
+    {
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+            return Ok(Some(path.to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+fn too_open(_token_path: &Path) -> Result<Option<PathBuf>> {
+    Ok(None)
+}
+
+#[cfg(unix)]
+fn create_private_dir(directory: &Path) -> std::io::Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(directory)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(directory: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(directory)
+}
+
+/// Close a directory of the tool created with looser permissions by an older version, and tell
+/// whether the directory is private.
+///
+/// Other directories, like one chosen with `SUREPET_HOME`, are left as they are.
+#[cfg(unix)]
+fn restrict_dir(directory: &Path) -> std::io::Result<bool> {
+    let mode = fs::metadata(directory)?.permissions().mode();
+    if mode & 0o077 == 0 {
+        return Ok(true);
+    }
+    if !own_dir(directory) {
+        return Ok(false);
+    }
+    fs::set_permissions(directory, fs::Permissions::from_mode(mode & 0o700))?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn restrict_dir(_directory: &Path) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+/// Whether the tool made the directory for itself: the state directory, unless it was chosen
+/// with `SUREPET_HOME`, or the directory of the profile tokens in it.
+#[cfg(unix)]
+fn own_dir(directory: &Path) -> bool {
+    match state_dir() {
+        Ok(state_dir) => {
+            directory == state_dir.join(PROFILE_TOKENS_PATH)
+                || (directory == state_dir && surepet_home().is_none())
         }
-        None => panic!("Impossible to get your home dir!"),
+        Err(_) => false,
+    }
+}
+
+fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    fn token() -> StoredToken {
+        StoredToken::new(
+            "some_token",
+            Some("me@example.com".to_string()),
+            "http://localhost:8080",
+        )
+    }
+
+    #[test]
+    fn it_reads_the_stored_token() {
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join(TOKEN_PATH);
+
+        assert_eq!(read_token(&token_path).unwrap(), None);
+        store_token(&token_path, &token()).unwrap();
+        let stored = read_token(&token_path).unwrap().unwrap();
+        assert_eq!(stored.token, "some_token");
+        assert_eq!(stored.email, Some("me@example.com".to_string()));
+        assert_eq!(stored.base_url, "http://localhost:8080");
+        assert!(stored.age() < Duration::from_secs(60));
+        forget_token(&token_path).unwrap();
+        assert_eq!(read_token(&token_path).unwrap(), None);
+    }
+
+    #[test]
+    fn it_ignores_tokens_in_the_old_format() {
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join(TOKEN_PATH);
+        store_token(&token_path, &token()).unwrap();
+        fs::write(&token_path, "some_token").unwrap();
+
+        assert_eq!(read_token(&token_path).unwrap(), None);
+    }
+
+    #[test]
+    fn it_migrates_legacy_tokens() {
+        let directory = tempfile::tempdir().unwrap();
+        let legacy_dir = directory.path().join(".surepet");
+        let state_dir = directory.path().join("state/surepet");
+        fs::create_dir_all(legacy_dir.join(PROFILE_TOKENS_PATH)).unwrap();
+        fs::write(legacy_dir.join(TOKEN_PATH), "legacy_token\n").unwrap();
+        let token = token();
+        fs::write(
+            legacy_dir.join(PROFILE_TOKENS_PATH).join("home"),
+            serde_json::to_string(&token).unwrap(),
+        )
+        .unwrap();
+
+        assert!(migrate_tokens(&legacy_dir, &state_dir).unwrap());
+
+        let migrated = read_token(&state_dir.join(TOKEN_PATH)).unwrap().unwrap();
+        assert_eq!(migrated.token, "legacy_token");
+        assert_eq!(migrated.email, None);
+        assert_eq!(migrated.base_url, BASE_URL);
+        assert_eq!(
+            read_token(&state_dir.join(PROFILE_TOKENS_PATH).join("home")).unwrap(),
+            Some(token)
+        );
+        assert!(!legacy_dir.exists());
+        assert!(!migrate_tokens(&legacy_dir, &state_dir).unwrap());
+    }
+
+    #[test]
+    fn it_migrates_only_on_first_use_of_the_state_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let legacy_dir = directory.path().join(".surepet");
+        let state_dir = directory.path().join("state/surepet");
+        store_token(&legacy_dir.join(PROFILE_TOKENS_PATH).join("home"), &token()).unwrap();
+        create_private_dir(&state_dir).unwrap();
+
+        assert!(!migrate_tokens(&legacy_dir, &state_dir).unwrap());
+
+        assert!(!state_dir.join(PROFILE_TOKENS_PATH).exists());
+        assert!(legacy_dir.join(PROFILE_TOKENS_PATH).join("home").exists());
+    }
+
+    #[test]
+    fn it_keeps_tokens_already_migrated() {
+        let directory = tempfile::tempdir().unwrap();
+        let legacy_dir = directory.path().join(".surepet");
+        let state_dir = directory.path().join("state/surepet");
+        store_token(&legacy_dir.join(TOKEN_PATH), &token()).unwrap();
+        store_token(
+            &state_dir.join(TOKEN_PATH),
+            &StoredToken::new("new_token", None, "http://localhost:8080"),
+        )
+        .unwrap();
+
+        assert!(!migrate_tokens(&legacy_dir, &state_dir).unwrap());
+
+        assert_eq!(
+            read_token(&state_dir.join(TOKEN_PATH))
+                .unwrap()
+                .unwrap()
+                .token,
+            "new_token"
+        );
+        assert!(legacy_dir.join(TOKEN_PATH).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_keeps_the_token_private() {
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join(TOKEN_PATH);
+        store_token(&token_path, &token()).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&token_path), 0o600);
+        assert_eq!(mode(token_path.parent().unwrap()), 0o700);
+        assert_eq!(
+            fs::read_dir(token_path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_ignores_tokens_other_users_can_read() {
+        let directory = tempfile::tempdir().unwrap();
+        let token_path = directory.path().join("surepet").join(TOKEN_PATH);
+        store_token(&token_path, &token()).unwrap();
+        fs::set_permissions(&token_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert_eq!(read_token(&token_path).unwrap(), None);
+
+        store_token(&token_path, &token()).unwrap();
+        assert!(read_token(&token_path).unwrap().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_closes_its_directories_left_open() {
+        let directory = tempfile::tempdir().unwrap();
+        temp_env::with_vars(
+            [
+                ("SUREPET_HOME", None),
+                ("XDG_STATE_HOME", Some(directory.path())),
+            ],
+            || {
+                let token_path = default_token_path().unwrap();
+                fs::create_dir_all(token_path.parent().unwrap()).unwrap();
+                fs::set_permissions(
+                    token_path.parent().unwrap(),
+                    fs::Permissions::from_mode(0o755),
+                )
+                .unwrap();
+
+                store_token(&token_path, &token()).unwrap();
+
+                assert!(read_token(&token_path).unwrap().is_some());
+            },
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_leaves_directories_of_the_user_open() {
+        let directory = tempfile::tempdir().unwrap();
+        let surepet_home = directory.path().join("project");
+        fs::create_dir_all(&surepet_home).unwrap();
+        fs::set_permissions(&surepet_home, fs::Permissions::from_mode(0o755)).unwrap();
+        temp_env::with_var("SUREPET_HOME", Some(&surepet_home), || {
+            let token_path = default_token_path().unwrap();
+
+            store_token(&token_path, &token()).unwrap();
+
+            assert!(!token_path.exists());
+            assert_eq!(
+                fs::metadata(&surepet_home).unwrap().permissions().mode() & 0o777,
+                0o755
+            );
+        });
     }
 }
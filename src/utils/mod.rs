@@ -1 +1,3 @@
 pub mod authentication;
+pub mod paths;
+pub mod time;
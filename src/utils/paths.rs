@@ -0,0 +1,135 @@
+use std::env;
+use std::path::PathBuf;
+
+use crate::error::Result;
+
+const APPLICATION: &str = "surepet";
+const CONFIG_FILE: &str = "config.toml";
+const LEGACY_DIR: &str = ".surepet";
+
+/// Home directory of the current user.
+pub fn home_dir() -> Result<PathBuf> {
+    Ok(home::home_dir().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Impossible to get your home dir!",
+        )
+    })?)
+}
+
+/// Where the CLI reads its settings from.
+pub fn config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(CONFIG_FILE))
+}
+
+/// Directory of the settings: `$SUREPET_HOME`, `$XDG_CONFIG_HOME/surepet` or `~/.config/surepet`.
+pub fn config_dir() -> Result<PathBuf> {
+    application_dir("XDG_CONFIG_HOME", ".config", None)
+}
+
+/// Directory of what must survive between two invocations, like tokens: `$SUREPET_HOME`,
+/// `$XDG_STATE_HOME/surepet` or `~/.local/state/surepet`.
+pub fn state_dir() -> Result<PathBuf> {
+    application_dir("XDG_STATE_HOME", ".local/state", None)
+}
+
+/// Directory of what can be thrown away: `$SUREPET_HOME/cache`, `$XDG_CACHE_HOME/surepet` or
+/// `~/.cache/surepet`.
+pub fn cache_dir() -> Result<PathBuf> {
+    application_dir("XDG_CACHE_HOME", ".cache", Some("cache"))
+}
+
+/// Directory used before XDG base directories were followed, unless `SUREPET_HOME` is set.
+pub fn legacy_dir() -> Result<Option<PathBuf>> {
+    if surepet_home().is_some() {
+        return Ok(None);
+    }
+    Ok(Some(home_dir()?.join(LEGACY_DIR)))
+}
+
+/// `SUREPET_HOME` (or one of its sub-directories) when set, or the `surepet` directory of the
+/// given XDG base directory.
+fn application_dir(
+    xdg_variable: &str,
+    default: &str,
+    surepet_home_subdir: Option<&str>,
+) -> Result<PathBuf> {
+    if let Some(surepet_home) = surepet_home() {
+        return Ok(match surepet_home_subdir {
+            Some(subdir) => surepet_home.join(subdir),
+            None => surepet_home,
+        });
+    }
+    let base = match absolute_path(xdg_variable) {
+        Some(base) => base,
+        None => home_dir()?.join(default),
+    };
+    Ok(base.join(APPLICATION))
+}
+
+/// Directory chosen with `SUREPET_HOME` to keep everything in, if any.
+pub fn surepet_home() -> Option<PathBuf> {
+    absolute_path("SUREPET_HOME")
+}
+
+/// Path held by an environment variable; relative ones are ignored, as the XDG specification says.
+fn absolute_path(variable: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn it_follows_xdg_base_directories() {
+        temp_env::with_vars(
+            [
+                ("SUREPET_HOME", None),
+                ("HOME", Some("/home/jane")),
+                ("XDG_CONFIG_HOME", Some("/xdg/config")),
+                ("XDG_STATE_HOME", Some("relative/state")),
+                ("XDG_CACHE_HOME", None),
+            ],
+            || {
+                assert_eq!(
+                    config_path().unwrap(),
+                    PathBuf::from("/xdg/config/surepet/config.toml")
+                );
+                assert_eq!(
+                    state_dir().unwrap(),
+                    PathBuf::from("/home/jane/.local/state/surepet")
+                );
+                assert_eq!(
+                    cache_dir().unwrap(),
+                    PathBuf::from("/home/jane/.cache/surepet")
+                );
+                assert_eq!(
+                    legacy_dir().unwrap(),
+                    Some(PathBuf::from("/home/jane/.surepet"))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_keeps_everything_in_surepet_home() {
+        temp_env::with_vars(
+            [
+                ("SUREPET_HOME", Some("/ci/surepet")),
+                ("XDG_CONFIG_HOME", Some("/xdg/config")),
+            ],
+            || {
+                assert_eq!(
+                    config_path().unwrap(),
+                    PathBuf::from("/ci/surepet/config.toml")
+                );
+                assert_eq!(state_dir().unwrap(), PathBuf::from("/ci/surepet"));
+                assert_eq!(cache_dir().unwrap(), PathBuf::from("/ci/surepet/cache"));
+                assert_eq!(legacy_dir().unwrap(), None);
+            },
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, FixedOffset, Local, LocalResult, NaiveDateTime, NaiveTime, TimeZone};
+
+/// Parse a point in time relative to `now`.
+///
+/// Accepted formats are:
+///
+/// - a duration ago: `10m`, `2h 30m`, `1day`
+/// - an RFC 3339 date: `2023-02-05T14:12:57+01:00`
+/// - a local date and time: `2023-02-05 14:12` or `2023-02-05 14:12:57`
+/// - a local time of the current day: `14:12`
+///
+/// Local dates are resolved with the offset of the local timezone at that date, which may differ
+/// from the one of `now` across a daylight saving time change. Local times skipped or repeated by
+/// such a change are rejected.
+pub fn parse_time(
+    value: &str,
+    now: DateTime<FixedOffset>,
+) -> Result<DateTime<FixedOffset>, String> {
+    let value = value.trim();
+
+    if let Ok(duration) = humantime::parse_duration(value) {
+        let duration = chrono::Duration::from_std(duration)
+            .map_err(|_| format!("`{}` is too far away", value))?;
+        return Ok(now - duration);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+    let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .ok()
+                .map(|time| now.date_naive().and_time(time))
+        });
+
+    match local.map(|time| Local.from_local_datetime(&time)) {
+        Some(LocalResult::Single(time)) => Ok(time.into()),
+        Some(LocalResult::Ambiguous(_, _)) => Err(format!(
+            "`{}` happens twice in the local timezone, give an RFC 3339 date instead",
+            value
+        )),
+        Some(LocalResult::None) => Err(format!(
+            "`{}` doesn't exist in the local timezone, as clocks skip it",
+            value
+        )),
+        None => Err(format!(
+            "`{}` is neither a duration like 10m, nor a date like 2023-02-05 14:12",
+            value
+        )),
+    }
+}
+
+/// Current time, in the local timezone.
+pub fn now() -> DateTime<FixedOffset> {
+    Local::now().into()
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    fn at(value: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(value).unwrap()
+    }
+
+    #[test]
+    fn it_parses_relative_times() {
+        let now = at("2023-01-01T12:00:00+01:00");
+
+        assert_eq!(parse_time("10m", now), Ok(at("2023-01-01T11:50:00+01:00")));
+        assert_eq!(
+            parse_time("1day 2h", now),
+            Ok(at("2022-12-31T10:00:00+01:00"))
+        );
+    }
+
+    #[test]
+    fn it_parses_absolute_times() {
+        temp_env::with_var("TZ", Some("Europe/Paris"), || {
+            let now = at("2023-01-01T12:00:00+01:00");
+
+            assert_eq!(
+                parse_time("2022-12-31T08:30:00+00:00", now),
+                Ok(at("2022-12-31T08:30:00+00:00"))
+            );
+            assert_eq!(
+                parse_time("2022-12-31 08:30", now),
+                Ok(at("2022-12-31T08:30:00+01:00"))
+            );
+            assert_eq!(
+                parse_time("08:30", now),
+                Ok(at("2023-01-01T08:30:00+01:00"))
+            );
+            assert!(parse_time("yesterday", now).is_err());
+        });
+    }
+
+    #[test]
+    fn it_parses_local_times_across_daylight_saving_time_changes() {
+        temp_env::with_var("TZ", Some("Europe/Paris"), || {
+            let now = at("2023-03-26T12:00:00+02:00");
+
+            assert_eq!(
+                parse_time("2023-03-25 12:00", now),
+                Ok(at("2023-03-25T12:00:00+01:00"))
+            );
+            assert_eq!(
+                parse_time("08:30", now),
+                Ok(at("2023-03-26T08:30:00+02:00"))
+            );
+            assert!(parse_time("2023-03-26 02:30", now).is_err());
+            assert!(parse_time("2023-10-29 02:30", now).is_err());
+        });
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::client::SurepetClient;
+use crate::entities::battery::BATTERY_PERCENT_LOW;
+use crate::entities::pet::{Pet, PetPosition};
+use crate::entities::shared::{Information, Record};
+use crate::entities::status::Status;
+use crate::error::{Result, SurepetError};
+
+/// Longest wait between two polls while the API keeps failing.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Polls the status of pets and devices at a regular interval, backing off while the API fails.
+pub struct Poller<'a> {
+    client: &'a SurepetClient,
+    interval: Duration,
+    /// How long to wait before the next poll, none before the first one.
+    wait: Option<Duration>,
+    /// How long to wait after the next failure.
+    backoff: Duration,
+}
+
+impl<'a> Poller<'a> {
+    pub fn new(client: &'a SurepetClient, interval: Duration) -> Self {
+        Poller {
+            client,
+            interval,
+            wait: None,
+            backoff: interval,
+        }
+    }
+
+    /// The current status, once the wait since the previous poll is over.
+    ///
+    /// When the API fails, the error is printed and `None` returned: the wait doubles after each
+    /// failure, up to `MAX_BACKOFF`. Missing or refused credentials stop polling, as they won't
+    /// get better by waiting.
+    pub async fn next(&mut self) -> Result<Option<Status>> {
+        if let Some(wait) = self.wait {
+            tokio::time::sleep(wait).await;
+        }
+
+        match self.client.status().await {
+            Ok(status) => {
+                self.wait = Some(self.interval);
+                self.backoff = self.interval;
+                Ok(Some(status))
+            }
+            Err(error @ (SurepetError::Unauthorized | SurepetError::MissingCredentials(_))) => {
+                Err(error)
+            }
+            Err(error) => {
+                eprintln!(
+                    "{}, retrying in {}",
+                    error,
+                    humantime::format_duration(self.backoff)
+                );
+                self.wait = Some(self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF.max(self.interval));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// What we know about pets and devices at a given time.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pets: Vec<Pet>,
+    devices: Vec<Record>,
+}
+
+impl Snapshot {
+    pub fn new(pets: Vec<Pet>, devices: &[Box<dyn Information>]) -> Self {
+        Snapshot {
+            pets,
+            devices: devices.iter().map(|device| device.record()).collect(),
+        }
+    }
+
+    /// What happened between this snapshot and a newer one.
+    ///
+    /// Pets and devices that only appear in one of the snapshots are ignored.
+    pub fn changes(&self, current: &Snapshot) -> Vec<Change> {
+        let mut changes = vec![];
+
+        for pet in &current.pets {
+            let previous = self.pets.iter().find(|previous| previous.id == pet.id);
+            if let Some(previous) = previous {
+                if previous.position != pet.position
+                    || previous.position_since != pet.position_since
+                {
+                    changes.push(Change::PetMoved {
+                        name: pet.name.clone(),
+                        position: pet.position,
+                        since: pet.position_since.with_timezone(&Local).into(),
+                    });
+                }
+            }
+        }
+
+        for device in &current.devices {
+            let previous = self
+                .devices
+                .iter()
+                .find(|previous| previous.kind == device.kind && previous.name == device.name);
+            if let Some(previous) = previous {
+                match (previous.online, device.online) {
+                    (Some(true), Some(false)) => changes.push(Change::Offline(device.name.clone())),
+                    (Some(false), Some(true)) => changes.push(Change::Online(device.name.clone())),
+                    _ => {}
+                }
+                if let (Some(before), Some(after)) =
+                    (previous.battery_percent, device.battery_percent)
+                {
+                    if before > BATTERY_PERCENT_LOW && after <= BATTERY_PERCENT_LOW {
+                        changes.push(Change::LowBattery(device.name.clone()));
+                    }
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+/// Something that happened to a pet or a device.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// A pet went inside or outside.
+    PetMoved {
+        name: String,
+        position: PetPosition,
+        since: DateTime<FixedOffset>,
+    },
+    /// A device got disconnected.
+    Offline(String),
+    /// A device got connected again.
+    Online(String),
+    /// The battery of a device fell under 10%.
+    LowBattery(String),
+}
+
+/// The kinds of changes, named as in machine-readable outputs and settings.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    PetMoved,
+    Offline,
+    Online,
+    LowBattery,
+}
+
+impl Change {
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            Change::PetMoved { .. } => ChangeKind::PetMoved,
+            Change::Offline(_) => ChangeKind::Offline,
+            Change::Online(_) => ChangeKind::Online,
+            Change::LowBattery(_) => ChangeKind::LowBattery,
+        }
+    }
+
+    /// Name of the pet or device that changed.
+    pub fn name(&self) -> &str {
+        match self {
+            Change::PetMoved { name, .. }
+            | Change::Offline(name)
+            | Change::Online(name)
+            | Change::LowBattery(name) => name,
+        }
+    }
+
+    /// Flat representation, used for machine-readable outputs.
+    ///
+    /// Example:
+    /// ```text
+    /// {"event": "pet_moved", "name": "Garfield", "position": "outside", "since": "2023-01-01T14:02:00+00:00"}
+    /// ```
+    pub fn payload(&self) -> Value {
+        let mut payload = json!({"event": self.kind(), "name": self.name()});
+        if let Change::PetMoved {
+            position, since, ..
+        } = self
+        {
+            payload["position"] = json!(position);
+            payload["since"] = json!(since.to_rfc3339());
+        }
+        payload
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::PetMoved {
+                name,
+                position: PetPosition::Unknown(position),
+                since,
+            } => write!(
+                f,
+                "❓ {} moved to an unknown position ({}) at {}",
+                name,
+                position,
+                since.format("%H:%M")
+            ),
+            Change::PetMoved {
+                name,
+                position,
+                since,
+            } => write!(
+                f,
+                "{} {} went {} at {}",
+                if *position == PetPosition::Inside {
+                    "🏠"
+                } else {
+                    "🏡"
+                },
+                name,
+                position.name(),
+                since.format("%H:%M")
+            ),
+            Change::Offline(name) => write!(f, "❌ {} went offline", name),
+            Change::Online(name) => write!(f, "✅ {} is back online", name),
+            Change::LowBattery(name) => write!(
+                f,
+                "🪫 {} battery dropped below {}%",
+                name, BATTERY_PERCENT_LOW
+            ),
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+    #[cfg(test)]
+    use crate::api::devices::DEVICES_PATH;
+    #[cfg(test)]
+    use crate::api::{authenticated_client, mocked_client};
+    #[cfg(test)]
+    use crate::entities::flap::Flap;
+    #[cfg(test)]
+    use crate::entities::hub::Hub;
+    #[cfg(test)]
+    use crate::entities::test_support;
+    #[cfg(test)]
+    use mockito::mock;
+
+    #[cfg(test)]
+    fn snapshot(position: PetPosition, online: bool, battery_voltage: f64) -> Snapshot {
+        Snapshot::new(
+            vec![Pet {
+                position,
+                position_since: match position {
+                    PetPosition::Inside => "2023-01-01T10:32:52+00:00",
+                    _ => "2023-01-01T14:02:00+00:00",
+                }
+                .parse()
+                .unwrap(),
+                ..test_support::pet()
+            }],
+            &[
+                Box::new(Hub {
+                    online,
+                    ..test_support::hub()
+                }),
+                Box::new(Flap {
+                    battery_voltage,
+                    ..test_support::flap()
+                }),
+            ],
+        )
+    }
+
+    #[test]
+    fn it_reports_nothing_when_nothing_changed() {
+        assert_eq!(
+            snapshot(PetPosition::Inside, true, 5.6).changes(&snapshot(
+                PetPosition::Inside,
+                true,
+                5.6
+            )),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn it_reports_changes() {
+        assert_eq!(
+            snapshot(PetPosition::Inside, true, 5.6).changes(&snapshot(
+                PetPosition::Outside,
+                false,
+                4.9
+            )),
+            vec![
+                Change::PetMoved {
+                    name: "Garfield".to_string(),
+                    position: PetPosition::Outside,
+                    since: "2023-01-01T14:02:00+00:00".parse().unwrap(),
+                },
+                Change::Offline("Hub".to_string()),
+                Change::LowBattery("Buanderie".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_low_battery_once() {
+        assert_eq!(
+            snapshot(PetPosition::Inside, false, 4.9).changes(&snapshot(
+                PetPosition::Inside,
+                true,
+                4.8
+            )),
+            vec![Change::Online("Hub".to_string())]
+        );
+    }
+
+    #[test]
+    fn it_formats_changes() {
+        assert_eq!(
+            Change::PetMoved {
+                name: "Garfield".to_string(),
+                position: PetPosition::Outside,
+                since: "2023-01-01T14:02:00+01:00".parse().unwrap(),
+            }
+            .to_string(),
+            "🏡 Garfield went outside at 14:02"
+        );
+        assert_eq!(
+            Change::LowBattery("Buanderie".to_string()).to_string(),
+            "🪫 Buanderie battery dropped below 10%"
+        );
+    }
+
+    #[test]
+    fn it_backs_off_while_the_api_fails() {
+        let _m = mock("GET", DEVICES_PATH).with_status(503).create();
+        let client = authenticated_client();
+        let mut poller = Poller::new(&client, Duration::from_secs(400));
+
+        assert!(matches!(tokio_test::block_on(poller.next()), Ok(None)));
+        assert_eq!(poller.wait, Some(Duration::from_secs(400)));
+        assert_eq!(poller.backoff, MAX_BACKOFF);
+    }
+
+    #[test]
+    fn it_stops_polling_without_credentials() {
+        temp_env::with_vars(
+            [("SUREPET_EMAIL", None::<&str>), ("SUREPET_PASSWORD", None)],
+            || {
+                let client = mocked_client();
+                let mut poller = Poller::new(&client, Duration::from_secs(30));
+
+                match tokio_test::block_on(poller.next()) {
+                    Err(SurepetError::MissingCredentials(_)) => {}
+                    other => panic!("Unexpected result: {:?}", other),
+                }
+            },
+        );
+    }
+}